@@ -0,0 +1,725 @@
+//! Raw Ethernet network interface in the style of ARINC 664 P7 (AFDX).
+//!
+//! Frames are sent and received using an `AF_PACKET` socket that is bound to a
+//! network device. The destination MAC address of each frame consists of a
+//! constant field followed by the 16-bit ID of the virtual link. The Ethernet
+//! payload contains the encoded [`Frame`]. Frames without VLAN tag are followed
+//! by a sequence number that is counted per virtual link.
+//!
+//! The interface is configured using the `source` and `destination` of its
+//! [`InterfaceConfig`]:
+//! - `source` is the name of the network device, optionally followed by a
+//!   slash and the source MAC address, e.g. `veth0/02:00:00:00:00:01`. The
+//!   hardware address of the device is used if no address is given.
+//! - `destination` is the constant field of the destination MAC address, e.g.
+//!   `03:00:00:00`.
+//!
+//! Creating the socket requires `CAP_NET_RAW`. The interface can be tried out
+//! without physical hardware using a veth pair inside of a network namespace:
+//!
+//! ```sh
+//! ip netns add router
+//! ip -n router link add veth0 type veth peer name veth1
+//! ip -n router link set veth0 up
+//! ip -n router link set veth1 up
+//! ```
+
+use a653rs_router::prelude::*;
+use core::{
+    cell::RefCell,
+    mem::{size_of, size_of_val},
+    ptr::{self, addr_of, addr_of_mut},
+    str::FromStr,
+};
+use std::{collections::HashMap, ffi::CString, fs, io, os::fd::RawFd};
+
+/// EtherType of the frames sent by the router.
+///
+/// This is the EtherType reserved for local experimental use.
+const ETHER_TYPE: u16 = 0x88B5;
+
+/// Length of an Ethernet header without VLAN tag.
+const ETH_HEADER_LEN: usize = 14;
+
+/// Length of a MAC address.
+const MAC_LEN: usize = 6;
+
+/// Maximum length of the payload of an Ethernet frame.
+const ETH_MAX_PAYLOAD: usize = 1500;
+
+/// Length of the sequence number following the encoded frame.
+const SEQUENCE_LEN: usize = size_of::<u8>();
+
+/// EtherType of an IEEE 802.1Q VLAN tag.
+const VLAN_ETHER_TYPE: u16 = 0x8100;
+
+/// Length of an IEEE 802.1Q VLAN tag.
+const VLAN_TAG_LEN: usize = 4;
+
+/// Bits of the tag control information containing the VLAN ID.
+const VLAN_VID_MASK: u16 = 0x0FFF;
+
+/// Position of the priority code point inside the tag control information.
+const VLAN_PCP_SHIFT: u16 = 13;
+
+/// Highest priority code point.
+const VLAN_MAX_PCP: u8 = 7;
+
+/// Socket option enabling the auxiliary data of received frames
+/// (`PACKET_AUXDATA` of `linux/if_packet.h`).
+const PACKET_AUXDATA: libc::c_int = 8;
+
+/// Status bit of the auxiliary data that is set if the network device removed
+/// a VLAN tag from the frame (`TP_STATUS_VLAN_VALID` of `linux/if_packet.h`).
+const TP_STATUS_VLAN_VALID: u32 = 1 << 4;
+
+/// Auxiliary data of a received frame (`struct tpacket_auxdata` of
+/// `linux/if_packet.h`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct AuxData {
+    status: u32,
+    len: u32,
+    snaplen: u32,
+    mac: u16,
+    net: u16,
+    vlan_tci: u16,
+    vlan_tpid: u16,
+}
+
+/// Constant field of the destination MAC address used by ARINC 664 P7.
+const DEFAULT_CONSTANT_FIELD: [u8; 4] = [0x03, 0x00, 0x00, 0x00];
+
+/// A MAC address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MacAddress(pub [u8; MAC_LEN]);
+
+impl FromStr for MacAddress {
+    type Err = InterfaceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mac = [0u8; MAC_LEN];
+        parse_octets(s, &mut mac)?;
+        Ok(Self(mac))
+    }
+}
+
+/// Parses octets separated by colons into `buf`, which must be filled exactly.
+fn parse_octets(s: &str, buf: &mut [u8]) -> Result<(), InterfaceError> {
+    let mut octets = s.trim().split(':');
+    for b in buf.iter_mut() {
+        let octet = octets.next().ok_or(InterfaceError::InvalidData)?;
+        *b = u8::from_str_radix(octet, 16).or(Err(InterfaceError::InvalidData))?;
+    }
+    if octets.next().is_some() {
+        return Err(InterfaceError::InvalidData);
+    }
+    Ok(())
+}
+
+/// Raw Ethernet network interface.
+#[derive(Debug)]
+pub struct EthernetNetworkInterface<const MTU: usize>;
+
+/// Raw Ethernet network interface that tags frames with an IEEE 802.1Q VLAN
+/// tag.
+///
+/// The ID of the virtual link is used as VLAN ID, so only IDs from 1 to 4094
+/// are supported. The priority of the virtual link is used as priority code
+/// point (PCP), where priorities above 7 are mapped to 7.
+#[derive(Debug)]
+pub struct VlanNetworkInterface<const MTU: usize>;
+
+static mut INTERFACES: Vec<EthernetSocket> = Vec::new();
+
+#[derive(Debug)]
+struct EthernetSocket {
+    fd: RawFd,
+    if_index: i32,
+    source: MacAddress,
+    constant_field: [u8; 4],
+    /// Whether frames carry a VLAN tag.
+    tagged: bool,
+    /// Next sequence number of each virtual link.
+    tx_sequence: RefCell<HashMap<u32, u8>>,
+    /// Last received sequence number of each virtual link.
+    rx_sequence: RefCell<HashMap<u32, u8>>,
+}
+
+impl EthernetSocket {
+    fn open(cfg: &InterfaceConfig, tagged: bool) -> Result<Self, InterfaceError> {
+        let (device, source) = match cfg.source.split_once('/') {
+            Some((device, mac)) => (device, Some(MacAddress::from_str(mac)?)),
+            None => (cfg.source.as_str(), None),
+        };
+        let source = match source {
+            Some(mac) => mac,
+            None => hardware_address(device)?,
+        };
+        let mut constant_field = DEFAULT_CONSTANT_FIELD;
+        if !cfg.destination.is_empty() {
+            parse_octets(&cfg.destination, &mut constant_field)?;
+        }
+        let name = CString::new(device).or(Err(InterfaceError::NotFound))?;
+        let if_index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if if_index == 0 {
+            router_debug!("Unknown network device {}", device);
+            return Err(InterfaceError::NotFound);
+        }
+        // Tagged frames are only delivered to sockets bound to all protocols if
+        // the tag is not removed by the network device.
+        let protocol = if tagged {
+            libc::ETH_P_ALL as u16
+        } else {
+            ETHER_TYPE
+        };
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                i32::from(protocol.to_be()),
+            )
+        };
+        if fd < 0 {
+            router_debug!(
+                "Failed to open packet socket: {}",
+                io::Error::last_os_error()
+            );
+            return Err(InterfaceError::NotFound);
+        }
+        let sock = Self {
+            fd,
+            if_index: if_index as i32,
+            source,
+            constant_field,
+            tagged,
+            tx_sequence: Default::default(),
+            rx_sequence: Default::default(),
+        };
+        let addr = sock.link_address(None);
+        let res = unsafe {
+            libc::bind(
+                fd,
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            router_debug!(
+                "Failed to bind to {}: {}",
+                device,
+                io::Error::last_os_error()
+            );
+            return Err(InterfaceError::NotFound);
+        }
+        // Network devices that offload VLAN processing remove the tag from
+        // received frames and only pass it along in the auxiliary data.
+        if tagged {
+            let enable: libc::c_int = 1;
+            let res = unsafe {
+                libc::setsockopt(
+                    fd,
+                    libc::SOL_PACKET,
+                    PACKET_AUXDATA,
+                    &enable as *const libc::c_int as *const libc::c_void,
+                    size_of::<libc::c_int>() as libc::socklen_t,
+                )
+            };
+            if res < 0 {
+                router_debug!(
+                    "Failed to enable auxiliary data: {}",
+                    io::Error::last_os_error()
+                );
+                return Err(InterfaceError::NotFound);
+            }
+        }
+        Ok(sock)
+    }
+
+    fn link_address(&self, dst: Option<&[u8; MAC_LEN]>) -> libc::sockaddr_ll {
+        let mut addr: libc::sockaddr_ll = unsafe { core::mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as u16;
+        addr.sll_protocol = if self.tagged {
+            (libc::ETH_P_ALL as u16).to_be()
+        } else {
+            ETHER_TYPE.to_be()
+        };
+        addr.sll_ifindex = self.if_index;
+        if let Some(dst) = dst {
+            addr.sll_halen = MAC_LEN as u8;
+            addr.sll_addr[..MAC_LEN].copy_from_slice(dst);
+        }
+        addr
+    }
+
+    /// The destination MAC address of a virtual link.
+    fn destination(&self, vl: VirtualLinkId) -> Result<[u8; MAC_LEN], InterfaceError> {
+        let vl = u16::try_from(vl.into_inner()).or(Err(InterfaceError::InvalidData))?;
+        let mut mac = [0u8; MAC_LEN];
+        mac[..4].copy_from_slice(&self.constant_field);
+        mac[4..].copy_from_slice(&vl.to_be_bytes());
+        Ok(mac)
+    }
+
+    /// Sequence numbers start at zero and wrap around to one, so that the
+    /// receiver can detect a restarted sender.
+    fn next_sequence(&self, vl: VirtualLinkId) -> u8 {
+        let mut seqs = self.tx_sequence.borrow_mut();
+        let seq = seqs.entry(vl.into_inner()).or_insert(0);
+        let current = *seq;
+        *seq = current.checked_add(1).unwrap_or(1);
+        current
+    }
+
+    fn check_sequence(&self, vl: VirtualLinkId, seq: u8) {
+        let mut seqs = self.rx_sequence.borrow_mut();
+        if let Some(last) = seqs.insert(vl.into_inner(), seq) {
+            let expected = last.checked_add(1).unwrap_or(1);
+            if seq != 0 && seq != expected {
+                router_debug!(
+                    "Unexpected sequence number {} on VL {}, expected {}",
+                    seq,
+                    vl,
+                    expected
+                );
+            }
+        }
+    }
+
+    /// Length of the sequence number following the encoded frame.
+    fn sequence_len(&self) -> usize {
+        if self.tagged {
+            0
+        } else {
+            SEQUENCE_LEN
+        }
+    }
+}
+
+impl Drop for EthernetSocket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+/// Reads the hardware address of a network device from sysfs.
+fn hardware_address(device: &str) -> Result<MacAddress, InterfaceError> {
+    let path = format!("/sys/class/net/{device}/address");
+    let addr = fs::read_to_string(path).or(Err(InterfaceError::NotFound))?;
+    MacAddress::from_str(&addr)
+}
+
+impl EthernetSocket {
+    fn receive<'a, const MTU: usize>(
+        &self,
+        buffer: &'a mut [u8],
+    ) -> Result<Frame<'a>, InterfaceError> {
+        let mut eth = [0u8; MTU];
+        // Skip frames of other protocols that are received by sockets which are
+        // bound to all protocols.
+        let (eth, tag) = loop {
+            let (read, pkttype, stripped) = self.receive_raw(&mut eth)?;
+            if pkttype == libc::PACKET_OUTGOING {
+                continue;
+            }
+            let eth = &eth[..read];
+            if let Some(tagged) = eth.get(..ETH_HEADER_LEN + VLAN_TAG_LEN) {
+                let ether_type = u16::from_be_bytes([tagged[12], tagged[13]]);
+                let inner = u16::from_be_bytes([tagged[16], tagged[17]]);
+                if ether_type == VLAN_ETHER_TYPE && inner == ETHER_TYPE {
+                    let tci = u16::from_be_bytes([tagged[14], tagged[15]]);
+                    break (strip_vlan_tag(eth), Some(tci));
+                }
+            }
+            if eth.len() >= ETH_HEADER_LEN && eth[12..ETH_HEADER_LEN] == ETHER_TYPE.to_be_bytes() {
+                break (eth.to_vec(), stripped);
+            }
+        };
+        if eth.len() < ETH_HEADER_LEN + self.sequence_len() {
+            return Err(InterfaceError::InvalidData);
+        }
+        let (header, payload) = eth.split_at(ETH_HEADER_LEN);
+        if header[..4] != self.constant_field {
+            return Err(InterfaceError::InvalidData);
+        }
+        let vl = VirtualLinkId::from(u16::from_be_bytes([header[4], header[5]]));
+        let (encoded, seq) = payload.split_at(payload.len() - self.sequence_len());
+        let frame = Frame::decode(encoded)?;
+        let vid = tag.map(|tci| u32::from(tci & VLAN_VID_MASK));
+        if frame.vl != vl || vid.is_some_and(|vid| vid != vl.into_inner()) {
+            router_debug!("VL {} of frame does not match its destination", frame.vl);
+            return Err(InterfaceError::InvalidData);
+        }
+        if let Some(seq) = seq.first() {
+            self.check_sequence(vl, *seq);
+        }
+        let msg = buffer
+            .get_mut(..frame.payload.len())
+            .ok_or(InterfaceError::InsufficientBuffer)?;
+        msg.copy_from_slice(frame.payload);
+        router_trace!("Received message from Ethernet socket");
+        Ok(Frame {
+            payload: msg,
+            priority: tag.map_or(0, |tci| (tci >> VLAN_PCP_SHIFT) as u8),
+            ..frame
+        })
+    }
+
+    /// Receives an Ethernet frame into `eth`.
+    ///
+    /// Returns the length and packet type of the frame and the tag control
+    /// information of the VLAN tag that the network device removed from it.
+    fn receive_raw(&self, eth: &mut [u8]) -> Result<(usize, u8, Option<u16>), InterfaceError> {
+        let mut addr: libc::sockaddr_ll = unsafe { core::mem::zeroed() };
+        let mut iov = libc::iovec {
+            iov_base: eth.as_mut_ptr().cast(),
+            iov_len: eth.len(),
+        };
+        // Large enough for a control message containing the auxiliary data
+        let mut control = [0usize; 8];
+        let mut msg: libc::msghdr = unsafe { core::mem::zeroed() };
+        msg.msg_name = &mut addr as *mut libc::sockaddr_ll as *mut libc::c_void;
+        msg.msg_namelen = size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = size_of_val(&control) as _;
+        let read = unsafe { libc::recvmsg(self.fd, &mut msg, 0) };
+        if read < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::WouldBlock {
+                return Err(InterfaceError::NoData);
+            }
+            router_debug!("Failed to receive from Ethernet socket: {:?}", err);
+            return Err(InterfaceError::InvalidData);
+        }
+        let mut tag = None;
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+        while let Some(hdr) = unsafe { cmsg.as_ref() } {
+            if hdr.cmsg_level == libc::SOL_PACKET && hdr.cmsg_type == PACKET_AUXDATA {
+                let aux: AuxData = unsafe { ptr::read_unaligned(libc::CMSG_DATA(hdr).cast()) };
+                if aux.status & TP_STATUS_VLAN_VALID != 0 {
+                    tag = Some(aux.vlan_tci);
+                }
+            }
+            cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+        }
+        Ok((read as usize, addr.sll_pkttype, tag))
+    }
+
+    fn send<const MTU: usize>(&self, frame: &Frame<'_>) -> Result<usize, InterfaceError> {
+        let dst = self.destination(frame.vl)?;
+        let mut eth = [0u8; MTU];
+        let header_len = if self.tagged {
+            ETH_HEADER_LEN + VLAN_TAG_LEN
+        } else {
+            ETH_HEADER_LEN
+        };
+        let (header, payload) = eth
+            .split_at_mut_checked(header_len)
+            .ok_or(InterfaceError::InsufficientBuffer)?;
+        header[..MAC_LEN].copy_from_slice(&dst);
+        header[MAC_LEN..2 * MAC_LEN].copy_from_slice(&self.source.0);
+        if self.tagged {
+            let vid = u16::try_from(frame.vl.into_inner()).or(Err(InterfaceError::InvalidData))?;
+            let tci = (u16::from(frame.priority.min(VLAN_MAX_PCP)) << VLAN_PCP_SHIFT) | vid;
+            header[12..14].copy_from_slice(&VLAN_ETHER_TYPE.to_be_bytes());
+            header[14..16].copy_from_slice(&tci.to_be_bytes());
+        }
+        header[header_len - 2..].copy_from_slice(&ETHER_TYPE.to_be_bytes());
+        let mut len = header_len + frame.encode(payload)?.len();
+        if !self.tagged {
+            let seq = eth.get_mut(len).ok_or(InterfaceError::InsufficientBuffer)?;
+            *seq = self.next_sequence(frame.vl);
+            len += SEQUENCE_LEN;
+        }
+        let addr = self.link_address(Some(&dst));
+        let sent = unsafe {
+            libc::sendto(
+                self.fd,
+                eth.as_ptr().cast(),
+                len,
+                0,
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if sent < 0 {
+            router_debug!(
+                "Failed to send to Ethernet socket: {:?}",
+                io::Error::last_os_error()
+            );
+            return Err(InterfaceError::SendFailed);
+        }
+        router_trace!("Send {} bytes to Ethernet socket", frame.payload.len());
+        Ok(sent as usize)
+    }
+}
+
+/// Removes the VLAN tag from a tagged Ethernet frame.
+fn strip_vlan_tag(eth: &[u8]) -> Vec<u8> {
+    let mut untagged = Vec::with_capacity(eth.len() - VLAN_TAG_LEN);
+    untagged.extend_from_slice(&eth[..2 * MAC_LEN]);
+    untagged.extend_from_slice(&eth[2 * MAC_LEN + VLAN_TAG_LEN..]);
+    untagged
+}
+
+impl<const MTU: usize> PlatformNetworkInterface for EthernetNetworkInterface<MTU> {
+    fn platform_interface_receive_unchecked(
+        id: NetworkInterfaceId,
+        buffer: &'_ mut [u8],
+    ) -> Result<Frame<'_>, InterfaceError> {
+        get_interface(id)?.receive::<MTU>(buffer)
+    }
+
+    fn platform_interface_send_unchecked(
+        id: NetworkInterfaceId,
+        frame: &Frame<'_>,
+    ) -> Result<usize, InterfaceError> {
+        get_interface(id)?.send::<MTU>(frame)
+    }
+}
+
+impl<const MTU: usize> PlatformNetworkInterface for VlanNetworkInterface<MTU> {
+    fn platform_interface_receive_unchecked(
+        id: NetworkInterfaceId,
+        buffer: &'_ mut [u8],
+    ) -> Result<Frame<'_>, InterfaceError> {
+        get_interface(id)?.receive::<MTU>(buffer)
+    }
+
+    fn platform_interface_send_unchecked(
+        id: NetworkInterfaceId,
+        frame: &Frame<'_>,
+    ) -> Result<usize, InterfaceError> {
+        get_interface(id)?.send::<MTU>(frame)
+    }
+
+    /// VLAN IDs 0 and 4095 are reserved.
+    fn supports_virtual_link(vl: &VirtualLinkId) -> bool {
+        (1..u32::from(VLAN_VID_MASK)).contains(&vl.into_inner())
+    }
+}
+
+/// This is only safe, because the interfaces are only used *after* the list of
+/// interfaces is created and the list of interfaces is never accessed
+/// concurrently.
+fn get_interface(id: NetworkInterfaceId) -> Result<&'static EthernetSocket, InterfaceError> {
+    unsafe {
+        (&*addr_of!(INTERFACES))
+            .get(id.0 as usize)
+            .ok_or(InterfaceError::NotFound)
+    }
+}
+
+// This is safe, because the interfaces are only created before the list of
+// interfaces is used.
+fn add_interface(s: EthernetSocket) -> Result<NetworkInterfaceId, InterfaceError> {
+    unsafe {
+        let id = NetworkInterfaceId((&*addr_of!(INTERFACES)).len() as u32);
+        (&mut *addr_of_mut!(INTERFACES)).push(s);
+        Ok(id)
+    }
+}
+
+/// Creates the socket of an interface if frames with a payload of up to the MTU
+/// of the configuration fit into Ethernet frames and the buffers of size `MTU`.
+fn create_socket<const MTU: usize>(
+    cfg: &InterfaceConfig,
+    tagged: bool,
+) -> Result<NetworkInterfaceId, InterfaceError> {
+    let (header, payload) = if tagged {
+        (
+            ETH_HEADER_LEN + VLAN_TAG_LEN,
+            Frame::MAX_HEADER_LEN + cfg.mtu,
+        )
+    } else {
+        (
+            ETH_HEADER_LEN,
+            Frame::MAX_HEADER_LEN + cfg.mtu + SEQUENCE_LEN,
+        )
+    };
+    if payload > ETH_MAX_PAYLOAD || header + payload > MTU {
+        return Err(InterfaceError::InsufficientBuffer);
+    }
+    add_interface(EthernetSocket::open(cfg, tagged)?)
+}
+
+impl<const MTU: usize> CreateNetworkInterfaceId<EthernetNetworkInterface<MTU>>
+    for EthernetNetworkInterface<MTU>
+{
+    fn create_network_interface_id(
+        cfg: &InterfaceConfig,
+    ) -> Result<NetworkInterfaceId, InterfaceError> {
+        create_socket::<MTU>(cfg, false)
+    }
+}
+
+impl<const MTU: usize> CreateNetworkInterfaceId<VlanNetworkInterface<MTU>>
+    for VlanNetworkInterface<MTU>
+{
+    fn create_network_interface_id(
+        cfg: &InterfaceConfig,
+    ) -> Result<NetworkInterfaceId, InterfaceError> {
+        create_socket::<MTU>(cfg, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{os::fd::AsRawFd, process::Command, thread::sleep, time::Duration};
+
+    const MTU: usize = 128;
+
+    #[test]
+    fn octets_are_parsed() {
+        let mut mac = [0u8; MAC_LEN];
+        parse_octets(" 02:00:0a:FF:00:01\n", &mut mac).unwrap();
+        assert_eq!(mac, [0x02, 0x00, 0x0A, 0xFF, 0x00, 0x01]);
+        for invalid in [
+            "02:00:0a:ff:00",
+            "02:00:0a:ff:00:01:02",
+            "02:00:0a:ff:00:gg",
+            "",
+        ] {
+            assert_eq!(
+                parse_octets(invalid, &mut mac),
+                Err(InterfaceError::InvalidData),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn vlan_tag_is_stripped() {
+        let tagged = [
+            [1u8; MAC_LEN].as_slice(),
+            &[2u8; MAC_LEN],
+            &VLAN_ETHER_TYPE.to_be_bytes(),
+            &[0x60, 0x05],
+            &ETHER_TYPE.to_be_bytes(),
+            b"payload",
+        ]
+        .concat();
+        let untagged = [
+            [1u8; MAC_LEN].as_slice(),
+            &[2u8; MAC_LEN],
+            &ETHER_TYPE.to_be_bytes(),
+            b"payload",
+        ]
+        .concat();
+        assert_eq!(strip_vlan_tag(&tagged), untagged);
+    }
+
+    #[test]
+    fn vlan_ids_are_limited_to_valid_range() {
+        let supported = |vl: u32| {
+            VlanNetworkInterface::<MTU>::supports_virtual_link(&VirtualLinkId::from_u32(vl))
+        };
+        assert!(!supported(0));
+        assert!(supported(1));
+        assert!(supported(4094));
+        assert!(!supported(4095));
+        assert!(!supported(u32::from(u16::MAX) + 1));
+    }
+
+    /// A network namespace containing a pair of connected veth devices that
+    /// is removed when dropped.
+    struct VethNamespace(&'static str);
+
+    impl VethNamespace {
+        /// Creates the namespace containing the pair `name` and `peer`.
+        fn create(name: &'static str, peer: &str) -> Self {
+            let ip = |args: &[&str]| {
+                let status = Command::new("ip").args(args).status();
+                assert!(status.is_ok_and(|s| s.success()), "ip {args:?} failed");
+            };
+            ip(&["netns", "add", name]);
+            let ns = Self(name);
+            ip(&[
+                "-n", name, "link", "add", name, "type", "veth", "peer", "name", peer,
+            ]);
+            ip(&["-n", name, "link", "set", name, "up"]);
+            ip(&["-n", name, "link", "set", peer, "up"]);
+            ns
+        }
+
+        /// Runs `f` on a thread that is moved into the namespace.
+        fn run(&self, f: impl FnOnce() + Send + 'static) {
+            let path = format!("/run/netns/{}", self.0);
+            std::thread::spawn(move || {
+                let ns = fs::File::open(path).unwrap();
+                let res = unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) };
+                assert_eq!(res, 0, "{}", io::Error::last_os_error());
+                f()
+            })
+            .join()
+            .unwrap();
+        }
+    }
+
+    impl Drop for VethNamespace {
+        fn drop(&mut self) {
+            _ = Command::new("ip").args(["netns", "del", self.0]).status();
+        }
+    }
+
+    /// Receives from `sock` until a frame arrives or receiving fails for
+    /// another reason than a lack of data.
+    fn receive(sock: &EthernetSocket) -> Result<(VirtualLinkId, u8, Vec<u8>), InterfaceError> {
+        let mut buf = [0u8; MTU];
+        for _ in 0..200 {
+            match sock.receive::<MTU>(&mut buf) {
+                Ok(frame) => return Ok((frame.vl, frame.priority, frame.payload.to_vec())),
+                Err(InterfaceError::NoData) => sleep(Duration::from_millis(1)),
+                Err(e) => return Err(e),
+            }
+        }
+        Err(InterfaceError::NoData)
+    }
+
+    fn exchange_frames(name: &'static str, peer: &'static str, tagged: bool) {
+        let ns = VethNamespace::create(name, peer);
+        ns.run(move || {
+            let config = |device: &str, mac: &str| {
+                InterfaceConfig::new(
+                    &format!("{device}/{mac}"),
+                    "03:00:00:00",
+                    DataRate::b(10_000_000),
+                    64,
+                )
+            };
+            let tx = EthernetSocket::open(&config(name, "02:00:00:00:00:01"), tagged).unwrap();
+            let rx = EthernetSocket::open(&config(peer, "02:00:00:00:00:02"), tagged).unwrap();
+            let vl = VirtualLinkId::from(5u16);
+            let frame = Frame {
+                priority: 3,
+                sequence: Some(1),
+                ..Frame::new(vl, b"ping")
+            };
+            let priority = if tagged { 3 } else { 0 };
+            for _ in 0..2 {
+                _ = tx.send::<MTU>(&frame).unwrap();
+                assert_eq!(receive(&rx), Ok((vl, priority, b"ping".to_vec())));
+            }
+            // Only frames without VLAN tag carry a sequence number
+            let seq = rx.rx_sequence.borrow().get(&5).copied();
+            assert_eq!(seq, (!tagged).then_some(1));
+            // Sent frames are not received by the sender
+            assert_eq!(receive(&tx), Err(InterfaceError::NoData));
+        });
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN to create a network namespace"]
+    fn frames_are_exchanged_over_veth_pair() {
+        exchange_frames("rtr-eth0", "rtr-eth1", false);
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN to create a network namespace"]
+    fn tagged_frames_are_exchanged_over_veth_pair() {
+        exchange_frames("rtr-vlan0", "rtr-vlan1", true);
+    }
+}
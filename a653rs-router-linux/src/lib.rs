@@ -1,11 +1,19 @@
 #[macro_use]
 mod macros;
 
+mod ethernet;
 mod network;
+mod serial;
+mod tcp;
+mod unix;
 
 #[cfg(feature = "trace")]
 mod trace;
 
 #[cfg(feature = "trace")]
 pub use crate::trace::LinuxTracer;
-pub use network::UdpNetworkInterface;
+pub use ethernet::{EthernetNetworkInterface, MacAddress, VlanNetworkInterface};
+pub use network::{MulticastConfig, PeerConfig, UdpConfig, UdpNetworkInterface};
+pub use serial::SerialNetworkInterface;
+pub use tcp::TcpNetworkInterface;
+pub use unix::UnixDatagramNetworkInterface;
@@ -1,9 +1,14 @@
 use a653rs::bindings::ApexPartitionP4;
 use a653rs::prelude::{ApexTimeP4Ext, Name, OperatingMode, Partition, PartitionExt, StartContext};
 use a653rs_linux::partition::{ApexLinuxPartition, ApexLogger};
-use a653rs_router::prelude::{RouterConfig, RouterState, VirtualLinksConfig};
+use a653rs_router::prelude::{
+    PortName, RouterConfig, RouterState, RoutingModesConfig, SchedulerConfig, VirtualLinksConfig,
+};
 use a653rs_router_linux::*;
-use core::str::FromStr;
+use core::{
+    ptr::{addr_of, addr_of_mut},
+    str::FromStr,
+};
 use std::{fs::File, io::BufReader};
 
 const MTU: usize = 2_000;
@@ -18,6 +23,9 @@ type NetIntf = UdpNetworkInterface<MTU>;
 
 static mut ROUTER: Option<RouterState<ApexLinuxPartition, NetIntf, INTERFACES, PORTS>> = None;
 static mut VL_CFG: Option<VirtualLinksConfig<INPUTS, OUTPUTS>> = None;
+static mut SCHEDULER_CFG: Option<SchedulerConfig> = None;
+static mut MODES_CFG: Option<RoutingModesConfig<INPUTS, OUTPUTS>> = None;
+static mut MODE_PORT: Option<PortName> = None;
 
 #[derive(Debug)]
 struct RouterPartition;
@@ -29,7 +37,10 @@ impl Partition<Hypervisor> for RouterPartition {
         let reader = BufReader::new(File::open(CONFIG_PATH).unwrap());
         let cfg: RouterConfig<INPUTS, OUTPUTS, INTERFACES, PORTS> =
             serde_yaml::from_reader(reader).unwrap();
-        _ = unsafe { VL_CFG.insert(cfg.virtual_links) };
+        _ = unsafe { (&mut *addr_of_mut!(VL_CFG)).insert(cfg.virtual_links) };
+        _ = unsafe { (&mut *addr_of_mut!(SCHEDULER_CFG)).insert(cfg.scheduler) };
+        _ = unsafe { (&mut *addr_of_mut!(MODES_CFG)).insert(cfg.modes) };
+        unsafe { *addr_of_mut!(MODE_PORT) = cfg.mode_port };
 
         let router = RouterState::create::<NetIntf>(
             ctx,
@@ -42,8 +53,8 @@ impl Partition<Hypervisor> for RouterPartition {
             entry_point,
         )
         .unwrap();
-        _ = unsafe { ROUTER.insert(router) };
-        let router = unsafe { ROUTER.as_ref() }.unwrap();
+        _ = unsafe { (&mut *addr_of_mut!(ROUTER)).insert(router) };
+        let router = unsafe { (&*addr_of!(ROUTER)).as_ref() }.unwrap();
         router.start().unwrap();
         <ApexLinuxPartition as ApexPartitionP4>::set_partition_mode(OperatingMode::Normal).unwrap();
     }
@@ -54,10 +65,19 @@ impl Partition<Hypervisor> for RouterPartition {
 }
 
 extern "C" fn entry_point() {
-    let router = unsafe { ROUTER.as_ref() }.unwrap();
-    let cfg = unsafe { VL_CFG.as_ref() }.unwrap().clone();
+    let router = unsafe { (&*addr_of!(ROUTER)).as_ref() }.unwrap();
+    let cfg = unsafe { (&*addr_of!(VL_CFG)).as_ref() }.unwrap().clone();
+    let scheduler_cfg = unsafe { (&*addr_of!(SCHEDULER_CFG)).as_ref() }.unwrap();
+    let modes_cfg = unsafe { (&*addr_of!(MODES_CFG)).as_ref() }.unwrap();
+    let mode_port = unsafe { (&*addr_of!(MODE_PORT)).as_ref() };
     let mut state = router
-        .router::<INPUTS, OUTPUTS, MTU>(cfg, &Hypervisor::get_time().unwrap_duration())
+        .modal_router::<INPUTS, OUTPUTS, MTU>(
+            cfg,
+            scheduler_cfg,
+            modes_cfg,
+            mode_port,
+            &Hypervisor::get_time().unwrap_duration(),
+        )
         .unwrap();
     loop {
         let res = state.forward::<MTU, _>(&ApexLinuxPartition);
@@ -67,8 +87,14 @@ extern "C" fn entry_point() {
             use log::{debug, trace};
 
             match res {
-                Ok(Some(v)) => {
-                    debug!("Forwarded VL {}", v);
+                Ok(Some(r)) if r.is_ok() => {
+                    debug!("Forwarded VL {}", r.vl);
+                    continue;
+                }
+                Ok(Some(r)) => {
+                    for (dst, e) in r.failures() {
+                        debug!("Failed to forward VL {} to {:?}: {}", r.vl, dst, e);
+                    }
                     continue;
                 }
                 Ok(None) => trace!("Scheduled no VL"),
@@ -85,6 +111,11 @@ extern "C" fn entry_point() {
         #[cfg(not(feature = "log"))]
         let _res = res;
         Hypervisor::periodic_wait().unwrap();
+        if let Err(_e) = state.read_mode_request() {
+            #[cfg(feature = "log")]
+            log::debug!("Failed to read mode request: {}", _e);
+        }
+        _ = state.apply_mode_request(&Hypervisor::get_time().unwrap_duration());
     }
 }
 
@@ -1,23 +1,200 @@
 use a653rs_linux::partition::ApexLinuxPartition;
 use a653rs_router::prelude::*;
-use std::net::UdpSocket;
+use core::{
+    cell::Cell,
+    ptr::{addr_of, addr_of_mut},
+    str::FromStr,
+};
+use std::{
+    io,
+    mem::size_of,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    os::fd::AsRawFd,
+};
 
+/// UDP network interface.
+///
+/// The interface is configured using the `destination` of its
+/// [`InterfaceConfig`], which is parsed into a [`UdpConfig`]:
+/// - An address, e.g. `127.0.0.1:5000`, connects the socket to it.
+/// - An IPv4 or IPv6 multicast group, e.g. `239.0.0.1:5000`, makes the socket
+///   join the group and each datagram is sent to all members of the group. The
+///   group may be followed by options separated by slashes, e.g.
+///   `239.0.0.1:5000/ttl=4/loop/if=10.0.0.1`:
+///   - `ttl=<hops>` sets the TTL or hop limit of sent datagrams, which is 1 by
+///     default.
+///   - `loop` loops sent datagrams back to the local host.
+///   - `if=<interface>` joins the group on the local interface with this IPv4
+///     address or, for IPv6 groups, this index. The kernel chooses the
+///     interface by default.
+/// - A list of peers separated by semicolons, each followed by an equals sign
+///   and the IDs of the virtual links that are sent to it separated by commas,
+///   e.g. `10.0.0.2:5000=1,2;10.0.0.3:5000=3`, leaves the socket unconnected.
+///   Each frame is sent to the peers of its virtual link and datagrams from
+///   other addresses than the peers are dropped and counted by
+///   [`UdpNetworkInterface::rejected`].
+///
+/// A socket joining a multicast group has to be bound to the port of the group,
+/// e.g. `0.0.0.0:5000` for the group `239.0.0.1:5000`. The datagrams are sent
+/// from a separate socket that is bound to an ephemeral port, so the interface
+/// drops the datagrams it sent itself if they are looped back.
 #[derive(Debug)]
 pub struct UdpNetworkInterface<const MTU: usize>;
 
 static mut INTERFACES: Vec<LimitedUdpSocket> = Vec::new();
 
+/// Options of a [`UdpNetworkInterface`], which are parsed from the
+/// `destination` of its [`InterfaceConfig`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UdpConfig {
+    /// Address the socket is connected to or multicast group it joins, if
+    /// any.
+    pub address: Option<String>,
+
+    /// Options for sending to a multicast group, if the address is one.
+    pub multicast: MulticastConfig,
+
+    /// Peers of an interface that is not connected to a single address.
+    ///
+    /// Frames are only accepted from these peers and are sent to the peers
+    /// of their virtual link.
+    pub peers: Vec<PeerConfig>,
+}
+
+impl FromStr for UdpConfig {
+    type Err = InterfaceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split('/');
+        let address = parts.next().unwrap_or_default();
+        let mut cfg = Self::default();
+        if address.contains('=') {
+            cfg.peers = address
+                .split(';')
+                .map(PeerConfig::from_str)
+                .collect::<Result<_, _>>()?;
+        } else if !address.is_empty() {
+            cfg.address = Some(address.to_string());
+        }
+        let is_group = address
+            .parse::<SocketAddr>()
+            .is_ok_and(|a| a.ip().is_multicast());
+        for option in parts {
+            if !is_group {
+                return Err(InterfaceError::InvalidData);
+            }
+            cfg.multicast.set(option)?;
+        }
+        Ok(cfg)
+    }
+}
+
+/// A peer of a [`UdpNetworkInterface`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerConfig {
+    /// Address of the peer.
+    pub address: SocketAddr,
+
+    /// Virtual links that are sent to the peer.
+    pub virtual_links: Vec<VirtualLinkId>,
+}
+
+impl FromStr for PeerConfig {
+    type Err = InterfaceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, virtual_links) = s.split_once('=').ok_or(InterfaceError::InvalidData)?;
+        let virtual_links = virtual_links
+            .split(',')
+            .filter(|vl| !vl.trim().is_empty())
+            .map(|vl| {
+                vl.trim()
+                    .parse()
+                    .map(VirtualLinkId::from_u32)
+                    .or(Err(InterfaceError::InvalidData))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            address: address
+                .trim()
+                .parse()
+                .or(Err(InterfaceError::InvalidData))?,
+            virtual_links,
+        })
+    }
+}
+
+/// Options for sending to a multicast group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastConfig {
+    /// Time-to-live or hop limit of sent datagrams.
+    pub ttl: u32,
+
+    /// Whether sent datagrams are looped back to the local host.
+    pub loopback: bool,
+
+    /// Address of the local interface an IPv4 group is joined on.
+    ///
+    /// The kernel chooses the interface if this is unspecified.
+    pub interface: Ipv4Addr,
+
+    /// Index of the local interface an IPv6 group is joined on.
+    ///
+    /// The kernel chooses the interface if this is zero.
+    pub interface_index: u32,
+}
+
+impl MulticastConfig {
+    /// Sets the option `option` of the destination.
+    fn set(&mut self, option: &str) -> Result<(), InterfaceError> {
+        match option.trim().split_once('=') {
+            None if option.trim() == "loop" => self.loopback = true,
+            Some(("ttl", ttl)) => self.ttl = ttl.parse().or(Err(InterfaceError::InvalidData))?,
+            Some(("if", interface)) => {
+                if let Ok(address) = interface.parse() {
+                    self.interface = address;
+                } else {
+                    self.interface_index =
+                        interface.parse().or(Err(InterfaceError::InvalidData))?;
+                }
+            }
+            _ => return Err(InterfaceError::InvalidData),
+        }
+        Ok(())
+    }
+}
+
+impl Default for MulticastConfig {
+    /// Datagrams do not leave the local network and are not looped back.
+    fn default() -> Self {
+        Self {
+            ttl: 1,
+            loopback: false,
+            interface: Ipv4Addr::UNSPECIFIED,
+            interface_index: 0,
+        }
+    }
+}
+
 impl<const MTU: usize> PlatformNetworkInterface for UdpNetworkInterface<MTU> {
     fn platform_interface_receive_unchecked(
         id: NetworkInterfaceId,
         buffer: &'_ mut [u8],
-    ) -> Result<&'_ [u8], InterfaceError> {
+    ) -> Result<Frame<'_>, InterfaceError> {
         let sock = get_interface(id)?;
-        match sock.sock.recv(buffer) {
+        let mut frame = [0u8; MTU];
+        match sock.recv(&mut frame) {
             Ok(read) => {
-                let msg = &buffer[..read];
+                let frame = Frame::decode(&frame[..read])?;
+                let msg = buffer
+                    .get_mut(..frame.payload.len())
+                    .ok_or(InterfaceError::InsufficientBuffer)?;
+                msg.copy_from_slice(frame.payload);
                 router_trace!("Received message from UDP socket");
-                Ok(msg)
+                Ok(Frame {
+                    payload: msg,
+                    ..frame
+                })
             }
             Err(_) => Err(InterfaceError::NoData),
         }
@@ -25,15 +202,16 @@ impl<const MTU: usize> PlatformNetworkInterface for UdpNetworkInterface<MTU> {
 
     fn platform_interface_send_unchecked(
         id: NetworkInterfaceId,
-        buffer: &[u8],
+        frame: &Frame<'_>,
     ) -> Result<usize, InterfaceError> {
         // This is safe, because the interfaces are only created before the list of
         // interfaces is used
         let sock = get_interface(id)?;
-        let res = sock.sock.send(buffer);
-        match res {
+        let mut buf = [0u8; MTU];
+        let encoded = frame.encode(&mut buf)?;
+        match sock.send(frame.vl, encoded) {
             Ok(trans) => {
-                router_trace!("Send {} bytes to UDP socket", buffer.len());
+                router_trace!("Send {} bytes to UDP socket", frame.payload.len());
                 Ok(trans)
             }
             Err(e) => {
@@ -49,7 +227,7 @@ impl<const MTU: usize> PlatformNetworkInterface for UdpNetworkInterface<MTU> {
 /// concurrently.
 fn get_interface(id: NetworkInterfaceId) -> Result<&'static LimitedUdpSocket, InterfaceError> {
     unsafe {
-        INTERFACES
+        (&*addr_of!(INTERFACES))
             .get(id.0 as usize)
             .ok_or(InterfaceError::NotFound)
     }
@@ -59,8 +237,8 @@ fn get_interface(id: NetworkInterfaceId) -> Result<&'static LimitedUdpSocket, In
 // interfaces is used.
 fn add_interface(s: LimitedUdpSocket) -> Result<NetworkInterfaceId, InterfaceError> {
     unsafe {
-        let id = NetworkInterfaceId(INTERFACES.len() as u32);
-        INTERFACES.push(s);
+        let id = NetworkInterfaceId((&*addr_of!(INTERFACES)).len() as u32);
+        (&mut *addr_of_mut!(INTERFACES)).push(s);
         Ok(id)
     }
 }
@@ -68,9 +246,110 @@ fn add_interface(s: LimitedUdpSocket) -> Result<NetworkInterfaceId, InterfaceErr
 #[derive(Debug)]
 struct LimitedUdpSocket {
     sock: UdpSocket,
+    /// Socket that is connected to the multicast group the datagrams are
+    /// sent to.
+    group: Option<UdpSocket>,
+    /// Source address of the datagrams sent to the group, which are dropped
+    /// if they are looped back.
+    own: Option<SocketAddr>,
+    /// Peers of an unconnected socket.
+    peers: Vec<PeerConfig>,
+    /// Datagrams that were dropped, because they were not sent by a peer.
+    rejected: Cell<usize>,
     _rate: DataRate,
 }
 
+impl LimitedUdpSocket {
+    fn open(sock: UdpSocket, cfg: &InterfaceConfig) -> Result<Self, InterfaceError> {
+        let udp = UdpConfig::from_str(&cfg.destination)?;
+        sock.set_nonblocking(true)
+            .or(Err(InterfaceError::SendFailed))?;
+        let address = udp.address.as_deref().unwrap_or_default();
+        let (group, own) = match address.parse::<SocketAddr>() {
+            _ if address.is_empty() => (None, None),
+            Ok(group) if group.ip().is_multicast() => {
+                let (group, own) = join_group(&sock, &group, &udp.multicast).map_err(|e| {
+                    router_debug!("Failed to join multicast group {}: {:?}", group, e);
+                    InterfaceError::SendFailed
+                })?;
+                (Some(group), Some(own))
+            }
+            _ => {
+                sock.connect(address).or(Err(InterfaceError::SendFailed))?;
+                (None, None)
+            }
+        };
+        Ok(Self {
+            sock,
+            group,
+            own,
+            peers: udp.peers,
+            rejected: Cell::new(0),
+            _rate: cfg.rate,
+        })
+    }
+
+    /// Receives the next datagram, skipping datagrams that were sent by the
+    /// socket itself or not sent by a peer.
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.peers.is_empty() && self.own.is_none() {
+            return self.sock.recv(buf);
+        }
+        loop {
+            let (read, src) = self.sock.recv_from(buf)?;
+            if self.own == Some(src) {
+                router_trace!("Dropping looped back datagram");
+                continue;
+            }
+            if self.peers.is_empty() || self.peers.iter().any(|p| p.address == src) {
+                return Ok(read);
+            }
+            router_debug!("Dropping datagram from unknown peer {}", src);
+            self.rejected.set(self.rejected.get() + 1);
+        }
+    }
+
+    /// Sends a frame to the peers of its virtual link, the multicast group or
+    /// the connected destination.
+    fn send(&self, vl: VirtualLinkId, encoded: &[u8]) -> io::Result<usize> {
+        if !self.peers.is_empty() {
+            self.send_to_peers(vl, encoded)
+        } else if let Some(group) = &self.group {
+            group.send(encoded)
+        } else {
+            self.sock.send(encoded)
+        }
+    }
+
+    /// Sends a frame to all peers of its virtual link.
+    ///
+    /// A peer that can not be reached does not prevent sending to the others.
+    /// Fails only if the frame could not be sent to any of them.
+    fn send_to_peers(&self, vl: VirtualLinkId, encoded: &[u8]) -> io::Result<usize> {
+        let mut res = None;
+        for p in self.peers.iter().filter(|p| p.virtual_links.contains(&vl)) {
+            let sent = self.sock.send_to(encoded, p.address).inspect_err(|e| {
+                router_debug!("Failed to send to peer {}: {:?}", p.address, e);
+            });
+            if !matches!(res, Some(Ok(_))) {
+                res = Some(sent);
+            }
+        }
+        res.unwrap_or_else(|| {
+            router_debug!("No peer for VL {}", vl);
+            Err(io::ErrorKind::NotFound.into())
+        })
+    }
+}
+
+impl<const MTU: usize> UdpNetworkInterface<MTU> {
+    /// Datagrams that were dropped by the interface, because they were not
+    /// sent by one of its `peers`.
+    pub fn rejected(id: NetworkInterfaceId) -> usize {
+        get_interface(id).map_or(0, |s| s.rejected.get())
+    }
+}
+
 fn get_socket(cfg: &InterfaceConfig) -> Result<UdpSocket, InterfaceError> {
     let res = ApexLinuxPartition::get_udp_socket(cfg.source.as_str());
     router_debug!("{:?}", cfg.source);
@@ -83,15 +362,206 @@ impl<const MTU: usize> CreateNetworkInterfaceId<UdpNetworkInterface<MTU>>
     fn create_network_interface_id(
         cfg: &InterfaceConfig,
     ) -> Result<NetworkInterfaceId, InterfaceError> {
-        let sock = get_socket(cfg)?;
-        sock.set_nonblocking(true)
-            .or(Err(InterfaceError::SendFailed))?;
-        sock.connect(cfg.destination.as_str())
-            .or(Err(InterfaceError::SendFailed))?;
-        let sock = LimitedUdpSocket {
-            sock,
-            _rate: cfg.rate,
-        };
+        if cfg.mtu + Frame::MAX_HEADER_LEN > MTU {
+            return Err(InterfaceError::InsufficientBuffer);
+        }
+        let sock = LimitedUdpSocket::open(get_socket(cfg)?, cfg)?;
         add_interface(sock)
     }
 }
+
+/// Joins a multicast group on the interface of `cfg` and opens the socket that
+/// sends to the group using this interface.
+///
+/// Returns the sending socket and its address.
+fn join_group(
+    sock: &UdpSocket,
+    group: &SocketAddr,
+    cfg: &MulticastConfig,
+) -> io::Result<(UdpSocket, SocketAddr)> {
+    let sender = match group.ip() {
+        IpAddr::V4(ip) => {
+            sock.join_multicast_v4(&ip, &cfg.interface)?;
+            let sender = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+            sender.set_multicast_ttl_v4(cfg.ttl)?;
+            sender.set_multicast_loop_v4(cfg.loopback)?;
+            if !cfg.interface.is_unspecified() {
+                let interface = libc::in_addr {
+                    s_addr: u32::from(cfg.interface).to_be(),
+                };
+                set_option(&sender, libc::IPPROTO_IP, libc::IP_MULTICAST_IF, &interface)?;
+            }
+            sender
+        }
+        IpAddr::V6(ip) => {
+            sock.join_multicast_v6(&ip, cfg.interface_index)?;
+            let sender = UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?;
+            sender.set_multicast_loop_v6(cfg.loopback)?;
+            // The hop limit and interface of IPv6 multicast are not exposed by
+            // the standard library.
+            let hops = cfg.ttl as libc::c_int;
+            set_option(
+                &sender,
+                libc::IPPROTO_IPV6,
+                libc::IPV6_MULTICAST_HOPS,
+                &hops,
+            )?;
+            if cfg.interface_index != 0 {
+                let index = cfg.interface_index as libc::c_int;
+                set_option(&sender, libc::IPPROTO_IPV6, libc::IPV6_MULTICAST_IF, &index)?;
+            }
+            sender
+        }
+    };
+    sender.set_nonblocking(true)?;
+    sender.connect(group)?;
+    let own = sender.local_addr()?;
+    Ok((sender, own))
+}
+
+/// Sets a socket option that is not exposed by the standard library.
+fn set_option<T>(
+    sock: &UdpSocket,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: &T,
+) -> io::Result<()> {
+    let res = unsafe {
+        libc::setsockopt(
+            sock.as_raw_fd(),
+            level,
+            name,
+            value as *const T as *const libc::c_void,
+            size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{os::fd::FromRawFd, thread::sleep, time::Duration};
+
+    /// Binds a UDP socket to `port` on all addresses, which other sockets may
+    /// be bound to as well.
+    fn bind_shared(port: u16) -> UdpSocket {
+        let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+        assert!(fd >= 0);
+        let sock = unsafe { UdpSocket::from_raw_fd(fd) };
+        let reuse: libc::c_int = 1;
+        let res = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_REUSEADDR,
+                &reuse as *const libc::c_int as *const libc::c_void,
+                size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        assert_eq!(res, 0);
+        let mut addr: libc::sockaddr_in = unsafe { core::mem::zeroed() };
+        addr.sin_family = libc::AF_INET as libc::sa_family_t;
+        addr.sin_port = port.to_be();
+        let res = unsafe {
+            libc::bind(
+                fd,
+                &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+                size_of::<libc::sockaddr_in>() as libc::socklen_t,
+            )
+        };
+        assert_eq!(res, 0, "{}", io::Error::last_os_error());
+        sock
+    }
+
+    /// Receives from `sock` until a datagram arrives.
+    fn recv(sock: &LimitedUdpSocket) -> Option<Vec<u8>> {
+        let mut buf = [0u8; 64];
+        for _ in 0..200 {
+            match sock.recv(&mut buf) {
+                Ok(read) => return Some(buf[..read].to_vec()),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => sleep(Duration::from_millis(1)),
+                Err(e) => panic!("{e}"),
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn udp_destination_is_parsed() {
+        let group = UdpConfig::from_str("239.0.0.1:5000/ttl=4/loop/if=10.0.0.1").unwrap();
+        assert_eq!(group.address.as_deref(), Some("239.0.0.1:5000"));
+        assert_eq!(
+            group.multicast,
+            MulticastConfig {
+                ttl: 4,
+                loopback: true,
+                interface: Ipv4Addr::new(10, 0, 0, 1),
+                ..Default::default()
+            }
+        );
+        let group = UdpConfig::from_str("[ff02::1]:5000/if=2").unwrap();
+        assert_eq!(group.multicast.interface_index, 2);
+
+        let peers = UdpConfig::from_str("10.0.0.2:5000=1,2;10.0.0.3:5000=3").unwrap();
+        assert_eq!(peers.address, None);
+        assert_eq!(
+            peers.peers,
+            [
+                PeerConfig {
+                    address: "10.0.0.2:5000".parse().unwrap(),
+                    virtual_links: vec![VirtualLinkId::from(1u16), VirtualLinkId::from(2u16)],
+                },
+                PeerConfig {
+                    address: "10.0.0.3:5000".parse().unwrap(),
+                    virtual_links: vec![VirtualLinkId::from(3u16)],
+                },
+            ]
+        );
+
+        assert_eq!(UdpConfig::from_str(""), Ok(UdpConfig::default()));
+        for invalid in [
+            "127.0.0.1:5000/loop",
+            "239.0.0.1:5000/ttl=x",
+            "239.0.0.1:5000/if=eth0",
+            "239.0.0.1:5000/unknown",
+            "10.0.0.2:5000=a",
+            "10.0.0.2=1",
+        ] {
+            assert_eq!(
+                UdpConfig::from_str(invalid),
+                Err(InterfaceError::InvalidData),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn udp_multicast_reaches_other_members_only() {
+        let port = UdpSocket::bind("0.0.0.0:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let cfg = InterfaceConfig::new(
+            &format!("0.0.0.0:{port}"),
+            &format!("239.255.0.1:{port}/loop/if=127.0.0.1"),
+            DataRate::b(10_000_000),
+            32,
+        );
+        let a = LimitedUdpSocket::open(bind_shared(port), &cfg).unwrap();
+        let b = LimitedUdpSocket::open(bind_shared(port), &cfg).unwrap();
+        let vl = VirtualLinkId::from(1u16);
+
+        assert_eq!(a.send(vl, b"ping").unwrap(), 4);
+        assert_eq!(recv(&b), Some(b"ping".to_vec()));
+        assert_eq!(recv(&a), None);
+
+        assert_eq!(b.send(vl, b"pong").unwrap(), 4);
+        assert_eq!(recv(&a), Some(b"pong".to_vec()));
+        assert_eq!(recv(&b), None);
+    }
+}
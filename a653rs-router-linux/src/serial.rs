@@ -0,0 +1,395 @@
+//! Serial TTY network interface.
+//!
+//! Frames are encoded using [`UartFrame`], so the interface can exchange frames
+//! with the UART network interface of `a653rs-router-zynq7000`, e.g. using a
+//! USB-serial adapter. The interface is configured using the `source` and
+//! `rate` of its [`InterfaceConfig`]:
+//! - `source` is the path of the TTY, e.g. `/dev/ttyUSB0`.
+//! - `rate` is the baud rate, e.g. `115200`. Only the standard baud rates from
+//!   9600 to 4000000 are supported.
+//!
+//! The TTY is put into raw mode using 8 data bits, no parity bit and one stop
+//! bit. The interface can be tested without hardware using a pair of
+//! pseudo-terminals:
+//!
+//! ```sh
+//! socat -d -d pty,raw,echo=0,link=/tmp/tty0 pty,raw,echo=0,link=/tmp/tty1
+//! ```
+
+use a653rs_router::prelude::*;
+use core::{
+    cell::RefCell,
+    ptr::{addr_of, addr_of_mut},
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+};
+
+/// Number of frames that are buffered for sending.
+const TX_FRAMES: usize = 4;
+
+/// Serial TTY network interface.
+#[derive(Debug)]
+pub struct SerialNetworkInterface<const MTU: usize>;
+
+static mut INTERFACES: Vec<SerialPort> = Vec::new();
+
+#[derive(Debug)]
+struct SerialPort {
+    tty: File,
+    /// Bytes of partially received frames.
+    rx: RefCell<Vec<u8>>,
+    /// Buffer for encoding the next frame.
+    encoded: RefCell<Vec<u8>>,
+    /// Bytes of frames that were not yet written to the TTY.
+    tx: RefCell<Vec<u8>>,
+    /// Maximum number of bytes in the send buffer.
+    tx_limit: usize,
+}
+
+impl SerialPort {
+    fn open(cfg: &InterfaceConfig, max_frame: usize) -> Result<Self, InterfaceError> {
+        let baud = cfg.rate.as_u64();
+        let speed = speed(baud).ok_or_else(|| {
+            router_debug!("Unsupported baud rate {}", baud);
+            InterfaceError::InvalidData
+        })?;
+        let tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open(cfg.source.as_str())
+            .map_err(|e| {
+                router_debug!("Failed to open {}: {}", cfg.source, e);
+                InterfaceError::NotFound
+            })?;
+        let fd = tty.as_raw_fd();
+        let mut termios: libc::termios = unsafe { core::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut termios) } < 0 {
+            router_debug!("{} is not a TTY", cfg.source);
+            return Err(InterfaceError::NotFound);
+        }
+        unsafe {
+            libc::cfmakeraw(&mut termios);
+            _ = libc::cfsetispeed(&mut termios, speed);
+            _ = libc::cfsetospeed(&mut termios, speed);
+        }
+        termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+        termios.c_cflag &= !(libc::CSTOPB | libc::PARENB | libc::CRTSCTS);
+        // Reads return immediately, even if no data is available.
+        termios.c_cc[libc::VMIN] = 0;
+        termios.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } < 0 {
+            router_debug!("Failed to configure {}", cfg.source);
+            return Err(InterfaceError::NotFound);
+        }
+        let tx_limit = TX_FRAMES * max_frame;
+        Ok(Self {
+            tty,
+            rx: RefCell::new(Vec::new()),
+            encoded: RefCell::new(vec![0u8; max_frame]),
+            tx: RefCell::new(Vec::with_capacity(tx_limit)),
+            tx_limit,
+        })
+    }
+
+    fn receive<'a, const MTU: usize>(
+        &self,
+        buffer: &'a mut [u8],
+    ) -> Result<Frame<'a>, InterfaceError> {
+        // Frames that did not fit into the output buffer of the TTY are written
+        // as soon as possible.
+        _ = self.flush();
+        let mut rx = self.rx.borrow_mut();
+        let mut chunk = [0u8; 64];
+        let end = loop {
+            if let Some(end) = rx.iter().position(|b| *b == UartFrame::<MTU>::TERMINATOR) {
+                break end;
+            }
+            let read = match (&self.tty).read(&mut chunk) {
+                Ok(0) => return Err(InterfaceError::NoData),
+                Ok(read) => read,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Err(InterfaceError::NoData),
+                Err(e) => {
+                    router_debug!("Failed to read from TTY: {}", e);
+                    return Err(InterfaceError::NoData);
+                }
+            };
+            rx.extend_from_slice(&chunk[..read]);
+            // Without a terminator, the buffered bytes can not be part of a
+            // valid frame.
+            if rx.len() > UartFrame::<MTU>::MAX_ENCODED_LEN
+                && !rx.contains(&UartFrame::<MTU>::TERMINATOR)
+            {
+                router_debug!("Discarding {} bytes without terminator", rx.len());
+                rx.clear();
+            }
+        };
+        let res = UartFrame::<MTU>::decode(&mut rx[..=end]).and_then(|frame| {
+            let msg = buffer
+                .get_mut(..frame.payload.len())
+                .ok_or(InterfaceError::InsufficientBuffer)?;
+            msg.copy_from_slice(frame.payload);
+            Ok(Frame {
+                payload: msg,
+                ..frame
+            })
+        });
+        _ = rx.drain(..=end);
+        router_trace!("Received message from TTY");
+        res
+    }
+
+    fn send<const MTU: usize>(&self, frame: &Frame<'_>) -> Result<usize, InterfaceError> {
+        let mut buf = self.encoded.borrow_mut();
+        let encoded = UartFrame::<MTU>::encode(frame, &mut buf)?;
+        {
+            let mut tx = self.tx.borrow_mut();
+            if tx.len() + encoded.len() > self.tx_limit {
+                router_debug!("TTY send buffer is full");
+                return Err(InterfaceError::SendFailed);
+            }
+            tx.extend_from_slice(encoded);
+        }
+        self.flush()?;
+        router_trace!("Send {} bytes to TTY", frame.payload.len());
+        Ok(encoded.len())
+    }
+
+    /// Writes as much of the send buffer to the TTY as possible.
+    fn flush(&self) -> Result<(), InterfaceError> {
+        let mut tx = self.tx.borrow_mut();
+        while !tx.is_empty() {
+            match (&self.tty).write(&tx) {
+                Ok(0) => break,
+                Ok(written) => _ = tx.drain(..written),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    // The receiver resynchronizes at the next terminator.
+                    router_debug!("Failed to write to TTY: {:?}", e);
+                    tx.clear();
+                    return Err(InterfaceError::SendFailed);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The termios speed of a baud rate.
+fn speed(baud: u64) -> Option<libc::speed_t> {
+    let speed = match baud {
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        460800 => libc::B460800,
+        921600 => libc::B921600,
+        1000000 => libc::B1000000,
+        2000000 => libc::B2000000,
+        3000000 => libc::B3000000,
+        4000000 => libc::B4000000,
+        _ => return None,
+    };
+    Some(speed)
+}
+
+impl<const MTU: usize> PlatformNetworkInterface for SerialNetworkInterface<MTU> {
+    fn platform_interface_receive_unchecked(
+        id: NetworkInterfaceId,
+        buffer: &'_ mut [u8],
+    ) -> Result<Frame<'_>, InterfaceError> {
+        get_interface(id)?.receive::<MTU>(buffer)
+    }
+
+    fn platform_interface_send_unchecked(
+        id: NetworkInterfaceId,
+        frame: &Frame<'_>,
+    ) -> Result<usize, InterfaceError> {
+        get_interface(id)?.send::<MTU>(frame)
+    }
+}
+
+/// This is only safe, because the interfaces are only used *after* the list of
+/// interfaces is created and the list of interfaces is never accessed
+/// concurrently.
+fn get_interface(id: NetworkInterfaceId) -> Result<&'static SerialPort, InterfaceError> {
+    unsafe {
+        (&*addr_of!(INTERFACES))
+            .get(id.0 as usize)
+            .ok_or(InterfaceError::NotFound)
+    }
+}
+
+// This is safe, because the interfaces are only created before the list of
+// interfaces is used.
+fn add_interface(s: SerialPort) -> Result<NetworkInterfaceId, InterfaceError> {
+    unsafe {
+        let id = NetworkInterfaceId((&*addr_of!(INTERFACES)).len() as u32);
+        (&mut *addr_of_mut!(INTERFACES)).push(s);
+        Ok(id)
+    }
+}
+
+impl<const MTU: usize> CreateNetworkInterfaceId<SerialNetworkInterface<MTU>>
+    for SerialNetworkInterface<MTU>
+{
+    fn create_network_interface_id(
+        cfg: &InterfaceConfig,
+    ) -> Result<NetworkInterfaceId, InterfaceError> {
+        if cfg.mtu > MTU {
+            return Err(InterfaceError::InsufficientBuffer);
+        }
+        add_interface(SerialPort::open(cfg, UartFrame::<MTU>::MAX_ENCODED_LEN)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        ffi::CStr,
+        os::fd::{FromRawFd, OwnedFd},
+        ptr::null_mut,
+        thread::sleep,
+        time::Duration,
+    };
+
+    const MTU: usize = 16;
+
+    /// Opens a serial port on a pseudo-terminal and returns it together with
+    /// the controlling side of the pseudo-terminal.
+    fn openpty() -> (SerialPort, File) {
+        let (port, controller) = openpty_at(DataRate::b(115200));
+        (port.unwrap(), controller)
+    }
+
+    /// Tries to open a serial port with a baud rate of `rate` on a
+    /// pseudo-terminal.
+    fn openpty_at(rate: DataRate) -> (Result<SerialPort, InterfaceError>, File) {
+        let (mut controller, mut tty) = (0, 0);
+        let res = unsafe {
+            libc::openpty(
+                &mut controller,
+                &mut tty,
+                null_mut(),
+                null_mut(),
+                null_mut(),
+            )
+        };
+        assert_eq!(res, 0, "{}", std::io::Error::last_os_error());
+        let (controller, tty) =
+            unsafe { (File::from_raw_fd(controller), OwnedFd::from_raw_fd(tty)) };
+        let mut name = [0; 64];
+        let res = unsafe { libc::ttyname_r(tty.as_raw_fd(), name.as_mut_ptr(), name.len()) };
+        assert_eq!(res, 0);
+        let name = unsafe { CStr::from_ptr(name.as_ptr()) }.to_str().unwrap();
+        let cfg = InterfaceConfig::new(name, "", rate, MTU);
+        let port = SerialPort::open(&cfg, UartFrame::<MTU>::MAX_ENCODED_LEN);
+        (port, controller)
+    }
+
+    fn encode(frame: &Frame<'_>) -> Vec<u8> {
+        let mut buf = [0u8; UartFrame::<MTU>::MAX_ENCODED_LEN];
+        UartFrame::<MTU>::encode(frame, &mut buf).unwrap().to_vec()
+    }
+
+    /// Receives from `port` until a frame arrives or receiving fails for
+    /// another reason than a lack of data.
+    fn receive(port: &SerialPort) -> Result<(VirtualLinkId, Vec<u8>), InterfaceError> {
+        let mut buf = [0u8; MTU];
+        for _ in 0..200 {
+            match port.receive::<MTU>(&mut buf) {
+                Ok(frame) => return Ok((frame.vl, frame.payload.to_vec())),
+                Err(InterfaceError::NoData) => sleep(Duration::from_millis(1)),
+                Err(e) => return Err(e),
+            }
+        }
+        Err(InterfaceError::NoData)
+    }
+
+    #[test]
+    fn serial_exchanges_frames_over_pty() {
+        let (port, mut controller) = openpty();
+        let vl = VirtualLinkId::from(1u16);
+
+        let frame = Frame::new(vl, b"ping");
+        let encoded = encode(&frame);
+        assert_eq!(port.send::<MTU>(&frame), Ok(encoded.len()));
+        let mut sent = vec![0u8; encoded.len()];
+        controller.read_exact(&mut sent).unwrap();
+        assert_eq!(sent, encoded);
+
+        // Partially received frames are completed by the next reads
+        let encoded = encode(&Frame::new(vl, b"pong"));
+        let (head, tail) = encoded.split_at(encoded.len() / 2);
+        controller.write_all(head).unwrap();
+        assert_eq!(receive(&port), Err(InterfaceError::NoData));
+        controller.write_all(tail).unwrap();
+        assert_eq!(receive(&port), Ok((vl, b"pong".to_vec())));
+    }
+
+    #[test]
+    fn serial_uses_rate_as_baud_rate() {
+        let (port, _controller) = openpty_at(DataRate::b(9600));
+        let port = port.unwrap();
+        let mut termios: libc::termios = unsafe { core::mem::zeroed() };
+        assert_eq!(
+            unsafe { libc::tcgetattr(port.tty.as_raw_fd(), &mut termios) },
+            0
+        );
+        assert_eq!(unsafe { libc::cfgetospeed(&termios) }, libc::B9600);
+
+        let (port, _controller) = openpty_at(DataRate::b(10_000));
+        assert_eq!(port.err(), Some(InterfaceError::InvalidData));
+    }
+
+    #[test]
+    fn serial_send_does_not_block_on_full_tty() {
+        let (port, controller) = openpty();
+        let frame = Frame::new(VirtualLinkId::from(1u16), b"ping");
+        let len = encode(&frame).len();
+
+        // The output buffer of the TTY and then the send buffer fill up
+        let sent = (0..100_000)
+            .take_while(|_| port.send::<MTU>(&frame).is_ok())
+            .count();
+        assert!(sent < 100_000);
+        assert_eq!(port.send::<MTU>(&frame), Err(InterfaceError::SendFailed));
+
+        // Buffered frames are written once the TTY is drained
+        let drain = || {
+            let mut buf = [0u8; 4096];
+            let mut total = 0;
+            loop {
+                match (&controller).read(&mut buf) {
+                    Ok(0) => return total,
+                    Ok(read) => total += read,
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => return total,
+                    Err(e) => panic!("{e}"),
+                }
+            }
+        };
+        let flags = unsafe { libc::fcntl(controller.as_raw_fd(), libc::F_GETFL) };
+        assert!(
+            unsafe {
+                libc::fcntl(
+                    controller.as_raw_fd(),
+                    libc::F_SETFL,
+                    flags | libc::O_NONBLOCK,
+                )
+            } >= 0
+        );
+        let mut received = drain();
+        while received < sent * len {
+            assert_eq!(receive(&port), Err(InterfaceError::NoData));
+            received += drain();
+        }
+        assert_eq!(received, sent * len);
+        assert!(port.send::<MTU>(&frame).is_ok());
+    }
+}
@@ -0,0 +1,550 @@
+//! TCP network interface.
+//!
+//! Each [`Frame`] is sent as its encoding prefixed by its length as a 16-bit
+//! big endian integer. The interface is configured using the `source` and
+//! `destination` of its [`InterfaceConfig`]:
+//! - If `destination` is empty, the interface listens on the `source` address
+//!   and accepts a single peer at a time.
+//! - Otherwise the interface connects to the `destination` address.
+//!
+//! If the connection is lost, the interface connects again. Connecting never
+//! blocks, so the attempt is completed by later calls to send or receive.
+//! Failed attempts to connect are repeated with an exponential backoff.
+//! Receiving never blocks.
+//! Partially received frames are buffered until the rest of the frame arrives.
+//! Frames that can not be written completely are buffered as well and are sent
+//! before any further frame.
+
+use a653rs_router::prelude::*;
+use core::{
+    cell::RefCell,
+    mem::{self, size_of},
+    ptr::{addr_of, addr_of_mut},
+};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    os::fd::{AsRawFd, FromRawFd},
+    time::{Duration, Instant},
+};
+
+/// Length of the length prefix of each frame.
+const LENGTH_LEN: usize = size_of::<u16>();
+
+/// Backoff after the first failed attempt to connect.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Maximum backoff between attempts to connect.
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Maximum time a single attempt to connect may take before it is abandoned.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Number of frames that are buffered for sending.
+const TX_FRAMES: usize = 4;
+
+/// TCP network interface.
+#[derive(Debug)]
+pub struct TcpNetworkInterface<const MTU: usize>;
+
+static mut INTERFACES: Vec<TcpInterface> = Vec::new();
+
+/// How the connection to the peer is established.
+#[derive(Debug)]
+enum Role {
+    /// Accept connections from the peer.
+    Server(TcpListener),
+    /// Connect to the peer.
+    Client(SocketAddr),
+}
+
+#[derive(Debug)]
+struct TcpInterface {
+    role: Role,
+    /// Maximum length of an encoded frame.
+    max_frame: usize,
+    /// Maximum number of bytes in the send buffer.
+    tx_limit: usize,
+    connection: RefCell<Connection>,
+}
+
+#[derive(Debug)]
+struct Connection {
+    stream: Option<TcpStream>,
+    /// Socket of an attempt to connect that has not completed yet and the
+    /// time the attempt was started.
+    connecting: Option<(TcpStream, Instant)>,
+    /// Bytes of partially received frames.
+    rx: Vec<u8>,
+    /// Bytes of frames that were not yet written to the stream.
+    tx: Vec<u8>,
+    /// Earliest time of the next attempt to connect.
+    retry_at: Instant,
+    /// Backoff after the next failed attempt to connect.
+    backoff: Duration,
+}
+
+impl TcpInterface {
+    fn open(cfg: &InterfaceConfig, max_frame: usize) -> Result<Self, InterfaceError> {
+        let role = if cfg.destination.is_empty() {
+            let listener = TcpListener::bind(cfg.source.as_str()).map_err(|e| {
+                router_debug!("Failed to listen on {}: {}", cfg.source, e);
+                InterfaceError::NotFound
+            })?;
+            listener
+                .set_nonblocking(true)
+                .or(Err(InterfaceError::NotFound))?;
+            Role::Server(listener)
+        } else {
+            let peer = cfg
+                .destination
+                .as_str()
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .ok_or(InterfaceError::NotFound)?;
+            Role::Client(peer)
+        };
+        let tx_limit = TX_FRAMES * (LENGTH_LEN + max_frame);
+        Ok(Self {
+            role,
+            max_frame,
+            tx_limit,
+            connection: RefCell::new(Connection {
+                stream: None,
+                connecting: None,
+                rx: Vec::with_capacity(LENGTH_LEN + max_frame),
+                tx: Vec::with_capacity(tx_limit),
+                retry_at: Instant::now(),
+                backoff: INITIAL_BACKOFF,
+            }),
+        })
+    }
+
+    /// Connects to the peer if necessary and returns whether there is a
+    /// connection.
+    fn connect(&self, conn: &mut Connection) -> bool {
+        if conn.stream.is_none() {
+            let stream = match &self.role {
+                Role::Server(listener) => listener.accept().ok().map(|(s, _)| s),
+                Role::Client(peer) => match Self::poll_connect(conn, peer) {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        router_debug!(
+                            "Failed to connect to {}, retrying in {:?}: {}",
+                            peer,
+                            conn.backoff,
+                            e
+                        );
+                        conn.retry_at = Instant::now() + conn.backoff;
+                        conn.backoff = (conn.backoff * 2).min(MAX_BACKOFF);
+                        None
+                    }
+                },
+            };
+            let Some(stream) = stream else {
+                return false;
+            };
+            if stream.set_nonblocking(true).is_err() || stream.set_nodelay(true).is_err() {
+                return false;
+            }
+            router_debug!("Connected to {:?}", stream.peer_addr());
+            conn.backoff = INITIAL_BACKOFF;
+            conn.stream = Some(stream);
+        }
+        true
+    }
+
+    /// Starts an attempt to connect to `peer` once the backoff has passed and
+    /// returns the stream once the attempt has completed.
+    fn poll_connect(conn: &mut Connection, peer: &SocketAddr) -> io::Result<Option<TcpStream>> {
+        let now = Instant::now();
+        let (socket, started) = match conn.connecting.take() {
+            Some(connecting) => connecting,
+            None if now < conn.retry_at => return Ok(None),
+            None => (start_connect(peer)?, now),
+        };
+        let mut fd = libc::pollfd {
+            fd: socket.as_raw_fd(),
+            events: libc::POLLOUT,
+            revents: 0,
+        };
+        let ready = unsafe { libc::poll(&mut fd, 1, 0) };
+        if ready < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if ready == 0 {
+            if now.duration_since(started) > CONNECT_TIMEOUT {
+                return Err(ErrorKind::TimedOut.into());
+            }
+            conn.connecting = Some((socket, started));
+            return Ok(None);
+        }
+        if let Some(e) = socket.take_error()? {
+            return Err(e);
+        }
+        Ok(Some(socket))
+    }
+
+    fn receive<'a>(&self, buffer: &'a mut [u8]) -> Result<Frame<'a>, InterfaceError> {
+        let mut conn = self.connection.borrow_mut();
+        let conn = &mut *conn;
+        if !self.connect(conn) {
+            return Err(InterfaceError::NoData);
+        }
+        // Buffered frames are sent even if no further frame is sent.
+        if conn.flush().is_err() {
+            return Err(InterfaceError::NoData);
+        }
+        // Only read up to the end of the next frame, so that the receive buffer
+        // never holds more than a single frame.
+        let missing = if conn.rx.len() < LENGTH_LEN {
+            LENGTH_LEN - conn.rx.len()
+        } else {
+            Self::frame_len(&conn.rx) - conn.rx.len()
+        };
+        let start = conn.rx.len();
+        conn.rx.resize(start + missing, 0);
+        let res = match conn.stream.as_mut() {
+            Some(stream) => stream.read(&mut conn.rx[start..]),
+            None => Err(ErrorKind::NotConnected.into()),
+        };
+        let read = match res {
+            Ok(0) => Err(io::Error::from(ErrorKind::UnexpectedEof)),
+            res => res,
+        };
+        match read {
+            Ok(read) => conn.rx.truncate(start + read),
+            Err(e) => {
+                conn.rx.truncate(start);
+                if e.kind() != ErrorKind::WouldBlock {
+                    router_debug!("Lost connection to TCP peer: {}", e);
+                    conn.disconnect();
+                }
+                return Err(InterfaceError::NoData);
+            }
+        }
+        match self.buffered_frame(&conn.rx) {
+            Ok(Some(len)) => Self::take_frame(conn, len, buffer),
+            Ok(None) => Err(InterfaceError::NoData),
+            Err(e) => {
+                // The start of the next frame can not be found anymore.
+                conn.disconnect();
+                Err(e)
+            }
+        }
+    }
+
+    /// Length of the frame in the receive buffer including its length prefix,
+    /// if it has been received completely.
+    fn buffered_frame(&self, rx: &[u8]) -> Result<Option<usize>, InterfaceError> {
+        if rx.len() < LENGTH_LEN {
+            return Ok(None);
+        }
+        let len = Self::frame_len(rx);
+        if len - LENGTH_LEN > self.max_frame {
+            router_debug!("TCP peer sent frame of {} bytes", len - LENGTH_LEN);
+            return Err(InterfaceError::InvalidData);
+        }
+        Ok((rx.len() >= len).then_some(len))
+    }
+
+    fn frame_len(rx: &[u8]) -> usize {
+        LENGTH_LEN + usize::from(u16::from_be_bytes([rx[0], rx[1]]))
+    }
+
+    fn take_frame<'a>(
+        conn: &mut Connection,
+        len: usize,
+        buffer: &'a mut [u8],
+    ) -> Result<Frame<'a>, InterfaceError> {
+        let res = Frame::decode(&conn.rx[LENGTH_LEN..len]).and_then(|frame| {
+            let msg = buffer
+                .get_mut(..frame.payload.len())
+                .ok_or(InterfaceError::InsufficientBuffer)?;
+            msg.copy_from_slice(frame.payload);
+            Ok(Frame {
+                payload: msg,
+                ..frame
+            })
+        });
+        conn.rx.drain(..len);
+        router_trace!("Received message from TCP stream");
+        res
+    }
+
+    fn send<const MTU: usize>(&self, frame: &Frame<'_>) -> Result<usize, InterfaceError> {
+        let mut buf = [0u8; MTU];
+        let encoded = frame.encode(&mut buf)?;
+        let len = u16::try_from(encoded.len()).or(Err(InterfaceError::InsufficientBuffer))?;
+        let mut conn = self.connection.borrow_mut();
+        let conn = &mut *conn;
+        if !self.connect(conn) {
+            return Err(InterfaceError::SendFailed);
+        }
+        if conn.tx.len() + LENGTH_LEN + encoded.len() > self.tx_limit {
+            router_debug!("TCP send buffer is full");
+            return Err(InterfaceError::SendFailed);
+        }
+        conn.tx.extend_from_slice(&len.to_be_bytes());
+        conn.tx.extend_from_slice(encoded);
+        conn.flush()?;
+        router_trace!("Send {} bytes to TCP stream", frame.payload.len());
+        Ok(encoded.len())
+    }
+}
+
+impl Connection {
+    /// Writes as much of the send buffer to the stream as possible.
+    fn flush(&mut self) -> Result<(), InterfaceError> {
+        let Some(stream) = self.stream.as_mut() else {
+            return Err(InterfaceError::SendFailed);
+        };
+        while !self.tx.is_empty() {
+            match stream.write(&self.tx) {
+                Ok(0) => {
+                    self.disconnect();
+                    return Err(InterfaceError::SendFailed);
+                }
+                Ok(written) => _ = self.tx.drain(..written),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    router_debug!("Lost connection to TCP peer: {}", e);
+                    self.disconnect();
+                    return Err(InterfaceError::SendFailed);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the connection and any partially transmitted frames, because the
+    /// stream of the next connection starts at a frame boundary.
+    fn disconnect(&mut self) {
+        self.stream = None;
+        self.rx.clear();
+        self.tx.clear();
+    }
+}
+
+/// Creates a non-blocking socket and starts to connect it to `peer`.
+fn start_connect(peer: &SocketAddr) -> io::Result<TcpStream> {
+    let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match peer {
+        SocketAddr::V4(peer) => {
+            let sin = unsafe { &mut *(&mut addr as *mut _ as *mut libc::sockaddr_in) };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = peer.port().to_be();
+            sin.sin_addr.s_addr = u32::from_ne_bytes(peer.ip().octets());
+            size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(peer) => {
+            let sin6 = unsafe { &mut *(&mut addr as *mut _ as *mut libc::sockaddr_in6) };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = peer.port().to_be();
+            sin6.sin6_flowinfo = peer.flowinfo();
+            sin6.sin6_addr.s6_addr = peer.ip().octets();
+            sin6.sin6_scope_id = peer.scope_id();
+            size_of::<libc::sockaddr_in6>()
+        }
+    };
+    let fd = unsafe {
+        libc::socket(
+            libc::c_int::from(addr.ss_family),
+            libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            0,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // The stream closes the socket if connecting fails
+    let stream = unsafe { TcpStream::from_raw_fd(fd) };
+    let res = unsafe {
+        libc::connect(
+            fd,
+            &addr as *const libc::sockaddr_storage as *const libc::sockaddr,
+            len as libc::socklen_t,
+        )
+    };
+    if res < 0 {
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::EINPROGRESS) {
+            return Err(e);
+        }
+    }
+    Ok(stream)
+}
+
+impl<const MTU: usize> PlatformNetworkInterface for TcpNetworkInterface<MTU> {
+    fn platform_interface_receive_unchecked(
+        id: NetworkInterfaceId,
+        buffer: &'_ mut [u8],
+    ) -> Result<Frame<'_>, InterfaceError> {
+        get_interface(id)?.receive(buffer)
+    }
+
+    fn platform_interface_send_unchecked(
+        id: NetworkInterfaceId,
+        frame: &Frame<'_>,
+    ) -> Result<usize, InterfaceError> {
+        get_interface(id)?.send::<MTU>(frame)
+    }
+}
+
+/// This is only safe, because the interfaces are only used *after* the list of
+/// interfaces is created and the list of interfaces is never accessed
+/// concurrently.
+fn get_interface(id: NetworkInterfaceId) -> Result<&'static TcpInterface, InterfaceError> {
+    unsafe {
+        (&*addr_of!(INTERFACES))
+            .get(id.0 as usize)
+            .ok_or(InterfaceError::NotFound)
+    }
+}
+
+// This is safe, because the interfaces are only created before the list of
+// interfaces is used.
+fn add_interface(s: TcpInterface) -> Result<NetworkInterfaceId, InterfaceError> {
+    unsafe {
+        let id = NetworkInterfaceId((&*addr_of!(INTERFACES)).len() as u32);
+        (&mut *addr_of_mut!(INTERFACES)).push(s);
+        Ok(id)
+    }
+}
+
+impl<const MTU: usize> CreateNetworkInterfaceId<TcpNetworkInterface<MTU>>
+    for TcpNetworkInterface<MTU>
+{
+    fn create_network_interface_id(
+        cfg: &InterfaceConfig,
+    ) -> Result<NetworkInterfaceId, InterfaceError> {
+        let max_frame = cfg.mtu + Frame::MAX_HEADER_LEN;
+        if max_frame > MTU || max_frame > usize::from(u16::MAX) {
+            return Err(InterfaceError::InsufficientBuffer);
+        }
+        add_interface(TcpInterface::open(cfg, max_frame)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    const MTU: usize = 64;
+
+    fn config(source: &str, destination: &str) -> InterfaceConfig {
+        InterfaceConfig::new(source, destination, DataRate::b(10_000_000), 32)
+    }
+
+    fn listen() -> (TcpInterface, SocketAddr) {
+        let server = TcpInterface::open(&config("127.0.0.1:0", ""), 32).unwrap();
+        let Role::Server(listener) = &server.role else {
+            unreachable!()
+        };
+        let addr = listener.local_addr().unwrap();
+        (server, addr)
+    }
+
+    /// Receives from `intf` until a frame arrives or receiving fails for
+    /// another reason than a lack of data.
+    fn receive(intf: &TcpInterface) -> Result<(VirtualLinkId, Vec<u8>), InterfaceError> {
+        let mut buf = [0u8; MTU];
+        for _ in 0..200 {
+            match intf.receive(&mut buf) {
+                Ok(frame) => return Ok((frame.vl, frame.payload.to_vec())),
+                Err(InterfaceError::NoData) => sleep(Duration::from_millis(1)),
+                Err(e) => return Err(e),
+            }
+        }
+        Err(InterfaceError::NoData)
+    }
+
+    /// Sends `frame` on `intf` once an attempt to connect has completed.
+    fn send(intf: &TcpInterface, frame: &Frame<'_>) -> Result<usize, InterfaceError> {
+        for _ in 0..200 {
+            match intf.send::<MTU>(frame) {
+                Err(InterfaceError::SendFailed)
+                    if intf.connection.borrow().connecting.is_some() =>
+                {
+                    sleep(Duration::from_millis(1))
+                }
+                res => return res,
+            }
+        }
+        Err(InterfaceError::SendFailed)
+    }
+
+    fn encode(frame: &Frame<'_>) -> Vec<u8> {
+        let mut buf = [0u8; MTU];
+        let encoded = frame.encode(&mut buf).unwrap();
+        let len = u16::try_from(encoded.len()).unwrap();
+        [&len.to_be_bytes(), encoded].concat()
+    }
+
+    #[test]
+    fn tcp_reassembles_partial_frames() {
+        let (server, addr) = listen();
+        assert_eq!(server.buffered_frame(&[0]), Ok(None));
+        assert_eq!(server.buffered_frame(&[0, 3, 0]), Ok(None));
+        assert_eq!(server.buffered_frame(&[0, 3, 0, 1, 0]), Ok(Some(5)));
+        assert_eq!(
+            server.buffered_frame(&[0, 33]),
+            Err(InterfaceError::InvalidData)
+        );
+
+        let vl = VirtualLinkId::from(7u16);
+        let encoded = encode(&Frame::new(vl, b"ping"));
+        let mut peer = TcpStream::connect(addr).unwrap();
+        let (first, rest) = encoded.split_at(3);
+        peer.write_all(first).unwrap();
+        assert_eq!(receive(&server), Err(InterfaceError::NoData));
+        peer.write_all(rest).unwrap();
+        assert_eq!(receive(&server), Ok((vl, b"ping".to_vec())));
+        assert!(server.connection.borrow().rx.is_empty());
+    }
+
+    #[test]
+    fn tcp_disconnects_on_oversized_frame() {
+        let (server, addr) = listen();
+        let mut peer = TcpStream::connect(addr).unwrap();
+        peer.write_all(&[0, 33]).unwrap();
+        assert_eq!(receive(&server), Err(InterfaceError::InvalidData));
+        assert!(server.connection.borrow().stream.is_none());
+        let mut buf = [0u8; 1];
+        assert_eq!(peer.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn tcp_client_reconnects_with_backoff() {
+        // Find a port nobody listens on
+        let (server, addr) = listen();
+        drop(server);
+        let client = TcpInterface::open(&config("", &addr.to_string()), 32).unwrap();
+        let vl = VirtualLinkId::from(1u16);
+        let frame = Frame::new(vl, b"ping");
+        assert_eq!(send(&client, &frame), Err(InterfaceError::SendFailed));
+        assert_eq!(client.connection.borrow().backoff, INITIAL_BACKOFF * 2);
+        // Attempts are not repeated before the backoff has passed
+        let listener = TcpListener::bind(addr).unwrap();
+        assert_eq!(client.send::<MTU>(&frame), Err(InterfaceError::SendFailed));
+        assert!(client.connection.borrow().connecting.is_none());
+
+        client.connection.borrow_mut().retry_at = Instant::now();
+        assert_eq!(send(&client, &frame), Ok(frame.encoded_len()));
+        assert_eq!(client.connection.borrow().backoff, INITIAL_BACKOFF);
+        let (mut peer, _) = listener.accept().unwrap();
+        let mut buf = vec![0u8; LENGTH_LEN + frame.encoded_len()];
+        peer.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, encode(&frame));
+
+        // The client connects again once the connection is lost
+        drop(peer);
+        assert_eq!(receive(&client), Err(InterfaceError::NoData));
+        let (mut peer, _) = listener.accept().unwrap();
+        assert_eq!(send(&client, &frame), Ok(frame.encoded_len()));
+        peer.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, encode(&frame));
+    }
+}
@@ -0,0 +1,241 @@
+//! Unix domain datagram socket network interface.
+//!
+//! The interface is configured using the `source` and `destination` of its
+//! [`InterfaceConfig`]:
+//! - `source` is the path the socket is bound to. A stale socket at this path
+//!   is removed before binding. Binding fails if the path exists but is not a
+//!   socket.
+//! - `destination` is the path of the socket of the peer.
+//!
+//! Access to the sockets is controlled by the permissions of their paths.
+
+use a653rs_router::prelude::*;
+use core::ptr::{addr_of, addr_of_mut};
+use std::{
+    fs,
+    io::ErrorKind,
+    os::unix::{fs::FileTypeExt, net::UnixDatagram},
+    path::PathBuf,
+};
+
+/// Unix domain datagram socket network interface.
+#[derive(Debug)]
+pub struct UnixDatagramNetworkInterface<const MTU: usize>;
+
+static mut INTERFACES: Vec<UnixSocket> = Vec::new();
+
+#[derive(Debug)]
+struct UnixSocket {
+    sock: UnixDatagram,
+    destination: PathBuf,
+}
+
+impl UnixSocket {
+    fn open(cfg: &InterfaceConfig) -> Result<Self, InterfaceError> {
+        if cfg.destination.is_empty() {
+            return Err(InterfaceError::NotFound);
+        }
+        let sock = bind(cfg.source.as_str())?;
+        sock.set_nonblocking(true)
+            .or(Err(InterfaceError::NotFound))?;
+        Ok(Self {
+            sock,
+            destination: PathBuf::from(cfg.destination.as_str()),
+        })
+    }
+
+    fn receive<'a, const MTU: usize>(
+        &self,
+        buffer: &'a mut [u8],
+    ) -> Result<Frame<'a>, InterfaceError> {
+        let mut frame = [0u8; MTU];
+        match self.sock.recv(&mut frame) {
+            Ok(read) => {
+                let frame = Frame::decode(&frame[..read])?;
+                let msg = buffer
+                    .get_mut(..frame.payload.len())
+                    .ok_or(InterfaceError::InsufficientBuffer)?;
+                msg.copy_from_slice(frame.payload);
+                router_trace!("Received message from Unix socket");
+                Ok(Frame {
+                    payload: msg,
+                    ..frame
+                })
+            }
+            Err(_) => Err(InterfaceError::NoData),
+        }
+    }
+
+    fn send<const MTU: usize>(&self, frame: &Frame<'_>) -> Result<usize, InterfaceError> {
+        let mut buf = [0u8; MTU];
+        let encoded = frame.encode(&mut buf)?;
+        match self.sock.send_to(encoded, &self.destination) {
+            Ok(trans) => {
+                router_trace!("Send {} bytes to Unix socket", frame.payload.len());
+                Ok(trans)
+            }
+            Err(e) => {
+                router_debug!(
+                    "Failed to send to Unix socket {:?}: {:?}",
+                    self.destination,
+                    e
+                );
+                Err(InterfaceError::SendFailed)
+            }
+        }
+    }
+}
+
+impl<const MTU: usize> PlatformNetworkInterface for UnixDatagramNetworkInterface<MTU> {
+    fn platform_interface_receive_unchecked(
+        id: NetworkInterfaceId,
+        buffer: &'_ mut [u8],
+    ) -> Result<Frame<'_>, InterfaceError> {
+        get_interface(id)?.receive::<MTU>(buffer)
+    }
+
+    fn platform_interface_send_unchecked(
+        id: NetworkInterfaceId,
+        frame: &Frame<'_>,
+    ) -> Result<usize, InterfaceError> {
+        get_interface(id)?.send::<MTU>(frame)
+    }
+}
+
+/// This is only safe, because the interfaces are only used *after* the list of
+/// interfaces is created and the list of interfaces is never accessed
+/// concurrently.
+fn get_interface(id: NetworkInterfaceId) -> Result<&'static UnixSocket, InterfaceError> {
+    unsafe {
+        (&*addr_of!(INTERFACES))
+            .get(id.0 as usize)
+            .ok_or(InterfaceError::NotFound)
+    }
+}
+
+// This is safe, because the interfaces are only created before the list of
+// interfaces is used.
+fn add_interface(s: UnixSocket) -> Result<NetworkInterfaceId, InterfaceError> {
+    unsafe {
+        let id = NetworkInterfaceId((&*addr_of!(INTERFACES)).len() as u32);
+        (&mut *addr_of_mut!(INTERFACES)).push(s);
+        Ok(id)
+    }
+}
+
+fn bind(path: &str) -> Result<UnixDatagram, InterfaceError> {
+    match fs::symlink_metadata(path) {
+        // Only sockets are removed, so a misconfigured path can not delete
+        // other files.
+        Ok(meta) if meta.file_type().is_socket() => {
+            fs::remove_file(path).map_err(|e| {
+                router_debug!("Failed to remove stale socket {}: {:?}", path, e);
+                InterfaceError::NotFound
+            })?;
+            router_debug!("Removed stale socket {}", path);
+        }
+        Ok(_) => {
+            router_debug!("Refusing to replace {}, which is not a socket", path);
+            return Err(InterfaceError::NotFound);
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => {
+            router_debug!("Failed to inspect {}: {:?}", path, e);
+            return Err(InterfaceError::NotFound);
+        }
+    }
+    UnixDatagram::bind(path).map_err(|e| {
+        router_debug!("Failed to bind to {}: {:?}", path, e);
+        InterfaceError::NotFound
+    })
+}
+
+impl<const MTU: usize> CreateNetworkInterfaceId<UnixDatagramNetworkInterface<MTU>>
+    for UnixDatagramNetworkInterface<MTU>
+{
+    fn create_network_interface_id(
+        cfg: &InterfaceConfig,
+    ) -> Result<NetworkInterfaceId, InterfaceError> {
+        if cfg.mtu + Frame::MAX_HEADER_LEN > MTU {
+            return Err(InterfaceError::InsufficientBuffer);
+        }
+        add_interface(UnixSocket::open(cfg)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{os::unix::net::UnixListener, path::Path, process};
+
+    const MTU: usize = 64;
+
+    /// A directory for the sockets of a test that is removed afterwards.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("{name}-{}", process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.0.join(name).to_str().unwrap().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn config(source: &str, destination: &str) -> InterfaceConfig {
+        InterfaceConfig::new(source, destination, DataRate::b(10_000_000), 32)
+    }
+
+    #[test]
+    fn unix_exchanges_frames() {
+        let dir = TempDir::new("unix-exchange");
+        let (a, b) = (dir.path("a"), dir.path("b"));
+        let sock_a = UnixSocket::open(&config(&a, &b)).unwrap();
+        let sock_b = UnixSocket::open(&config(&b, &a)).unwrap();
+        let mut buf = [0u8; MTU];
+        assert_eq!(sock_b.receive::<MTU>(&mut buf), Err(InterfaceError::NoData));
+
+        let frame = Frame::new(VirtualLinkId::from(1u16), b"ping");
+        assert_eq!(sock_a.send::<MTU>(&frame), Ok(frame.encoded_len()));
+        assert_eq!(sock_b.receive::<MTU>(&mut buf), Ok(frame));
+        let frame = Frame::new(VirtualLinkId::from(2u16), b"pong");
+        assert_eq!(sock_b.send::<MTU>(&frame), Ok(frame.encoded_len()));
+        assert_eq!(sock_a.receive::<MTU>(&mut buf), Ok(frame));
+    }
+
+    #[test]
+    fn unix_replaces_stale_socket() {
+        let dir = TempDir::new("unix-stale");
+        let (a, b) = (dir.path("a"), dir.path("b"));
+        // A socket left behind by a previous run of the router
+        drop(UnixListener::bind(&a).unwrap());
+        assert!(Path::new(&a).exists());
+        let sock_a = UnixSocket::open(&config(&a, &b)).unwrap();
+        let sock_b = UnixSocket::open(&config(&b, &a)).unwrap();
+        let frame = Frame::new(VirtualLinkId::from(1u16), b"ping");
+        sock_b.send::<MTU>(&frame).unwrap();
+        let mut buf = [0u8; MTU];
+        assert_eq!(sock_a.receive::<MTU>(&mut buf), Ok(frame));
+    }
+
+    #[test]
+    fn unix_does_not_replace_other_files() {
+        let dir = TempDir::new("unix-file");
+        let (a, b) = (dir.path("a"), dir.path("b"));
+        fs::write(&a, b"data").unwrap();
+        assert_eq!(
+            UnixSocket::open(&config(&a, &b)).err(),
+            Some(InterfaceError::NotFound)
+        );
+        assert_eq!(fs::read(&a).unwrap(), b"data");
+    }
+}
@@ -1,3 +1,5 @@
+pub mod loopback;
+
 pub mod test_data {
     pub const CFG: &str = r##"
 period:
@@ -45,8 +47,32 @@ use a653rs::bindings::{
     QueuingPortId, Validity,
 };
 use a653rs_router::prelude::{
-    CreateNetworkInterfaceId, NetworkInterfaceId, PlatformNetworkInterface,
+    CreateNetworkInterfaceId, Frame, NetworkInterfaceId, PlatformNetworkInterface, VirtualLinkId,
 };
+use std::sync::Mutex;
+
+/// Names of the sampling ports and their last message. The ID of a port is its
+/// index plus one.
+static SAMPLING_PORTS: Mutex<Vec<(String, Option<Vec<u8>>)>> = Mutex::new(Vec::new());
+
+fn sampling_port_id(name: &str) -> a653rs::prelude::SamplingPortId {
+    let mut ports = SAMPLING_PORTS.lock().unwrap();
+    let index = match ports.iter().position(|(n, _)| n == name) {
+        Some(index) => index,
+        None => {
+            ports.push((name.to_string(), None));
+            ports.len() - 1
+        }
+    };
+    (index + 1) as a653rs::prelude::SamplingPortId
+}
+
+/// Writes a message to the sampling port `name`, as if it was written by
+/// another partition.
+pub fn write_sampling_port(name: &str, message: &[u8]) {
+    let id = sampling_port_id(name);
+    DummyHypervisor::write_sampling_message(id, message).unwrap();
+}
 
 #[derive(Debug)]
 pub struct DummyHypervisor;
@@ -102,29 +128,47 @@ impl ApexQueuingPortP4 for DummyHypervisor {
 
 impl ApexSamplingPortP4 for DummyHypervisor {
     fn create_sampling_port(
-        _sampling_port_name: a653rs::bindings::SamplingPortName,
+        sampling_port_name: a653rs::bindings::SamplingPortName,
         _max_message_size: a653rs::prelude::MessageSize,
         _port_direction: a653rs::bindings::PortDirection,
         _refresh_period: a653rs::bindings::ApexSystemTime,
     ) -> Result<a653rs::prelude::SamplingPortId, a653rs::bindings::ErrorReturnCode> {
-        Ok(1)
+        let len = sampling_port_name
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(sampling_port_name.len());
+        let name = String::from_utf8_lossy(&sampling_port_name[..len]);
+        Ok(sampling_port_id(&name))
     }
 
     fn write_sampling_message(
-        _sampling_port_id: a653rs::prelude::SamplingPortId,
-        _message: &[a653rs::prelude::ApexByte],
+        sampling_port_id: a653rs::prelude::SamplingPortId,
+        message: &[a653rs::prelude::ApexByte],
     ) -> Result<(), a653rs::bindings::ErrorReturnCode> {
+        let mut ports = SAMPLING_PORTS.lock().unwrap();
+        if let Some((_, last)) = ports.get_mut(sampling_port_id as usize - 1) {
+            *last = Some(message.to_vec());
+        }
         Ok(())
     }
 
+    /// Returns the last message of the port or a message of one byte if none
+    /// was written yet.
     unsafe fn read_sampling_message(
-        _sampling_port_id: a653rs::prelude::SamplingPortId,
-        _message: &mut [a653rs::prelude::ApexByte],
+        sampling_port_id: a653rs::prelude::SamplingPortId,
+        message: &mut [a653rs::prelude::ApexByte],
     ) -> Result<
         (a653rs::prelude::Validity, a653rs::prelude::MessageSize),
         a653rs::bindings::ErrorReturnCode,
     > {
-        Ok((Validity::Valid, MessageSize::from(1u32)))
+        let ports = SAMPLING_PORTS.lock().unwrap();
+        match ports.get(sampling_port_id as usize - 1) {
+            Some((_, Some(last))) => {
+                message[..last.len()].copy_from_slice(last);
+                Ok((Validity::Valid, last.len() as MessageSize))
+            }
+            _ => Ok((Validity::Valid, MessageSize::from(1u32))),
+        }
     }
 }
 
@@ -178,7 +222,7 @@ pub struct DummyNetIntf;
 impl PlatformNetworkInterface for DummyNetIntf {
     fn platform_interface_send_unchecked(
         _id: NetworkInterfaceId,
-        _buffer: &[u8],
+        _frame: &Frame<'_>,
     ) -> Result<usize, a653rs_router::prelude::InterfaceError> {
         Ok(1)
     }
@@ -186,8 +230,8 @@ impl PlatformNetworkInterface for DummyNetIntf {
     fn platform_interface_receive_unchecked(
         _id: NetworkInterfaceId,
         buffer: &'_ mut [u8],
-    ) -> Result<&'_ [u8], a653rs_router::prelude::InterfaceError> {
-        Ok(buffer)
+    ) -> Result<Frame<'_>, a653rs_router::prelude::InterfaceError> {
+        Ok(Frame::new(VirtualLinkId::from(1u16), buffer))
     }
 }
 
@@ -0,0 +1,117 @@
+//! In-memory network interface for host-side tests.
+//!
+//! Interfaces are connected by named channels, that hold the encoded frames in
+//! the order they were sent. The `source` of the [`InterfaceConfig`] of an
+//! interface names the channel it receives from and the `destination` names the
+//! channel it sends to. Two routers are connected by using the `destination`
+//! of one interface as the `source` of the other and vice versa.
+//!
+//! All channels are shared by the whole process, so tests running in parallel
+//! should use distinct channel names.
+
+use a653rs_router::prelude::{
+    CreateNetworkInterfaceId, Frame, InterfaceConfig, InterfaceError, NetworkInterfaceId,
+    PlatformNetworkInterface, VirtualLinkId,
+};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+};
+
+static CHANNELS: Mutex<BTreeMap<String, VecDeque<Vec<u8>>>> = Mutex::new(BTreeMap::new());
+
+static INTERFACES: Mutex<Vec<Link>> = Mutex::new(Vec::new());
+
+#[derive(Debug)]
+struct Link {
+    source: String,
+    destination: String,
+    mtu: usize,
+}
+
+/// In-memory network interface.
+#[derive(Debug)]
+pub struct LoopbackNetIntf;
+
+/// Sends a frame to a channel, as if it was sent by a network interface.
+pub fn inject(channel: &str, frame: &Frame<'_>) -> Result<(), InterfaceError> {
+    let mut buf = vec![0u8; frame.encoded_len()];
+    let encoded = frame.encode(&mut buf)?;
+    push(channel, encoded.to_vec());
+    Ok(())
+}
+
+/// Takes the oldest frame from a channel, as if it was received by a network
+/// interface, and returns its virtual link and payload.
+pub fn take(channel: &str) -> Option<(VirtualLinkId, Vec<u8>)> {
+    let encoded = pop(channel)?;
+    let frame = Frame::decode(&encoded).ok()?;
+    Some((frame.vl, frame.payload.to_vec()))
+}
+
+fn push(channel: &str, encoded: Vec<u8>) {
+    CHANNELS
+        .lock()
+        .unwrap()
+        .entry(channel.to_string())
+        .or_default()
+        .push_back(encoded);
+}
+
+fn pop(channel: &str) -> Option<Vec<u8>> {
+    CHANNELS.lock().unwrap().get_mut(channel)?.pop_front()
+}
+
+fn link<T>(id: NetworkInterfaceId, f: impl FnOnce(&Link) -> T) -> Result<T, InterfaceError> {
+    let interfaces = INTERFACES.lock().unwrap();
+    let link = interfaces
+        .get(id.0 as usize)
+        .ok_or(InterfaceError::NotFound)?;
+    Ok(f(link))
+}
+
+impl PlatformNetworkInterface for LoopbackNetIntf {
+    fn platform_interface_send_unchecked(
+        id: NetworkInterfaceId,
+        frame: &Frame<'_>,
+    ) -> Result<usize, InterfaceError> {
+        let (destination, mtu) = link(id, |l| (l.destination.clone(), l.mtu))?;
+        if frame.payload.len() > mtu {
+            return Err(InterfaceError::InsufficientBuffer);
+        }
+        inject(&destination, frame)?;
+        Ok(frame.payload.len())
+    }
+
+    fn platform_interface_receive_unchecked(
+        id: NetworkInterfaceId,
+        buffer: &'_ mut [u8],
+    ) -> Result<Frame<'_>, InterfaceError> {
+        let source = link(id, |l| l.source.clone())?;
+        let encoded = pop(&source).ok_or(InterfaceError::NoData)?;
+        let frame = Frame::decode(&encoded)?;
+        let msg = buffer
+            .get_mut(..frame.payload.len())
+            .ok_or(InterfaceError::InsufficientBuffer)?;
+        msg.copy_from_slice(frame.payload);
+        Ok(Frame {
+            payload: msg,
+            ..frame
+        })
+    }
+}
+
+impl CreateNetworkInterfaceId<LoopbackNetIntf> for LoopbackNetIntf {
+    fn create_network_interface_id(
+        cfg: &InterfaceConfig,
+    ) -> Result<NetworkInterfaceId, InterfaceError> {
+        let mut interfaces = INTERFACES.lock().unwrap();
+        let id = NetworkInterfaceId(interfaces.len() as u32);
+        interfaces.push(Link {
+            source: cfg.source.to_string(),
+            destination: cfg.destination.to_string(),
+            mtu: cfg.mtu,
+        });
+        Ok(id)
+    }
+}
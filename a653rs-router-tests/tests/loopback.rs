@@ -0,0 +1,222 @@
+use a653rs::prelude::{Name, Partition, PartitionExt, StartContext};
+use a653rs_router::prelude::{
+    Error, Frame, InvalidTimeError, RouteError, RouterConfig, RouterState, TimeSource,
+    VirtualLinkId,
+};
+use a653rs_router_tests::{
+    loopback::{self, LoopbackNetIntf},
+    DummyHypervisor,
+};
+use core::str::FromStr;
+use std::{process::exit, time::Duration};
+
+const MTU: usize = 100;
+
+const NODE_A: &str = r##"
+period:
+  secs: 1
+  nanos: 0
+time_capacity:
+  secs: 0
+  nanos: 300000
+stack_size: 10000
+virtual_links:
+  1:
+    period:
+      secs: 0
+      nanos: 10000
+    source: "Host"
+    destinations: [ "NodeB" ]
+    sequence_numbers: true
+interfaces:
+  Host:
+    source: "a-in"
+    destination: "a-out"
+    mtu: 100
+    rate: 10000000
+  NodeB:
+    source: "b-a"
+    destination: "a-b"
+    mtu: 100
+    rate: 10000000
+"##;
+
+const NODE_B: &str = r##"
+period:
+  secs: 1
+  nanos: 0
+time_capacity:
+  secs: 0
+  nanos: 300000
+stack_size: 10000
+virtual_links:
+  1:
+    period:
+      secs: 0
+      nanos: 10000
+    source: "NodeA"
+    destinations: [ "Host" ]
+    sequence_numbers: true
+interfaces:
+  Host:
+    source: "b-in"
+    destination: "b-out"
+    mtu: 100
+    rate: 10000000
+  NodeA:
+    source: "a-b"
+    destination: "b-a"
+    mtu: 100
+    rate: 10000000
+"##;
+
+/// Two virtual links that share a network interface and may each forward
+/// several messages at once. The rate of the host interface is not limited, so
+/// it is always ready for another message.
+const SHARED: &str = r##"
+period:
+  secs: 1
+  nanos: 0
+time_capacity:
+  secs: 0
+  nanos: 300000
+stack_size: 10000
+virtual_links:
+  1:
+    period:
+      secs: 0
+      nanos: 10000
+    source: "Net"
+    destinations: [ "Host" ]
+    max_messages: 4
+  2:
+    period:
+      secs: 0
+      nanos: 10000
+    source: "Net"
+    destinations: [ "Host" ]
+    max_messages: 4
+interfaces:
+  Host:
+    source: "c-in"
+    destination: "c-out"
+    mtu: 100
+    rate: 0
+  Net:
+    source: "net-c"
+    destination: "c-net"
+    mtu: 100
+    rate: 10000000
+"##;
+
+#[derive(Debug)]
+struct FixedTime(Duration);
+
+impl TimeSource for FixedTime {
+    fn get_time(&self) -> Result<Duration, InvalidTimeError> {
+        Ok(self.0)
+    }
+}
+
+#[derive(Debug)]
+struct LoopbackPartition;
+
+impl Partition<DummyHypervisor> for LoopbackPartition {
+    fn cold_start(&self, ctx: &mut StartContext<DummyHypervisor>) {
+        let node_a: RouterConfig<2, 2, 2, 2> = serde_yaml::from_str(NODE_A).unwrap();
+        let node_b: RouterConfig<2, 2, 2, 2> = serde_yaml::from_str(NODE_B).unwrap();
+        let state_a = create(ctx, "NodeA", &node_a);
+        let state_b = create(ctx, "NodeB", &node_b);
+        let start = Duration::ZERO;
+        let mut router_a = state_a
+            .router::<2, 2, MTU>(node_a.virtual_links, &node_a.scheduler, &start)
+            .unwrap();
+        let mut router_b = state_b
+            .router::<2, 2, MTU>(node_b.virtual_links, &node_b.scheduler, &start)
+            .unwrap();
+
+        let vl = VirtualLinkId::from(1u16);
+        loopback::inject("a-in", &Frame::new(vl, b"ping")).unwrap();
+        let time = FixedTime(Duration::from_millis(1));
+        let report = router_a.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert!(report.is_ok());
+        let report = router_b.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert!(report.is_ok());
+        assert_eq!(loopback::take("b-out"), Some((vl, b"ping".to_vec())));
+        assert_eq!(loopback::take("b-out"), None);
+        assert_eq!(loopback::take("a-b"), None);
+
+        // Router B drops repeated frames and counts skipped sequence numbers
+        let frame = Frame {
+            sequence: Some(5),
+            ..Frame::new(vl, b"pong")
+        };
+        loopback::inject("a-b", &frame).unwrap();
+        loopback::inject("a-b", &frame).unwrap();
+        let time = FixedTime(Duration::from_millis(2));
+        let report = router_b.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert!(report.is_ok());
+        let time = FixedTime(Duration::from_millis(3));
+        assert_eq!(
+            router_b.forward::<MTU, _>(&time),
+            Err(Error::Route(RouteError::Duplicate))
+        );
+        assert_eq!(loopback::take("b-out"), Some((vl, b"pong".to_vec())));
+        let stats = router_b.stats().virtual_link(&vl).unwrap();
+        assert_eq!((stats.lost, stats.duplicates), (4, 1));
+        assert_eq!(stats.dropped, 0);
+
+        // Messages of another virtual link that are drained from a shared
+        // interface are reported separately
+        let shared: RouterConfig<2, 2, 2, 2> = serde_yaml::from_str(SHARED).unwrap();
+        let state_c = create(ctx, "NodeC", &shared);
+        let mut router_c = state_c
+            .router::<2, 2, MTU>(shared.virtual_links, &shared.scheduler, &start)
+            .unwrap();
+        let (vl_1, vl_2) = (VirtualLinkId::from(1u16), VirtualLinkId::from(2u16));
+        loopback::inject("net-c", &Frame::new(vl_1, b"ping")).unwrap();
+        loopback::inject("net-c", &Frame::new(vl_1, b"pong")).unwrap();
+        loopback::inject("net-c", &Frame::new(vl_2, b"pang")).unwrap();
+        let time = FixedTime(Duration::from_millis(1));
+        let report = router_c.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert_eq!((report.vl, report.messages), (vl_1, 2));
+        let report = router_c.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert_eq!((report.vl, report.messages), (vl_2, 1));
+        assert_eq!(loopback::take("c-out"), Some((vl_1, b"ping".to_vec())));
+        assert_eq!(loopback::take("c-out"), Some((vl_1, b"pong".to_vec())));
+        assert_eq!(loopback::take("c-out"), Some((vl_2, b"pang".to_vec())));
+
+        println!("success");
+        exit(0)
+    }
+
+    fn warm_start(&self, ctx: &mut StartContext<DummyHypervisor>) {
+        self.cold_start(ctx)
+    }
+}
+
+fn create(
+    ctx: &mut StartContext<DummyHypervisor>,
+    name: &str,
+    cfg: &RouterConfig<2, 2, 2, 2>,
+) -> RouterState<DummyHypervisor, LoopbackNetIntf, 2, 2> {
+    RouterState::create::<LoopbackNetIntf>(
+        ctx,
+        Name::from_str(name).unwrap(),
+        cfg.interfaces.clone(),
+        cfg.ports.clone(),
+        cfg.period,
+        cfg.time_capacity,
+        cfg.stack_size,
+        entry_point,
+    )
+    .unwrap()
+}
+
+// Not called by DummyHypervisor
+extern "C" fn entry_point() {}
+
+#[test]
+fn main() {
+    LoopbackPartition.run();
+}
@@ -0,0 +1,158 @@
+use a653rs::prelude::{Name, Partition, PartitionExt, StartContext};
+use a653rs_router::prelude::{
+    Frame, InvalidTimeError, RouterConfig, RouterState, TimeSource, VirtualLinkId, DEFAULT_MODE,
+};
+use a653rs_router_tests::{
+    loopback::{self, LoopbackNetIntf},
+    write_sampling_port, DummyHypervisor,
+};
+use core::str::FromStr;
+use std::{process::exit, time::Duration};
+
+const MTU: usize = 100;
+
+const CFG: &str = r##"
+period:
+  secs: 0
+  nanos: 10000000
+time_capacity:
+  secs: 0
+  nanos: 300000
+stack_size: 10000
+virtual_links:
+  1:
+    period:
+      secs: 0
+      nanos: 10000000
+    source: "Host"
+    destinations: [ "Net" ]
+modes:
+  degraded:
+    virtual_links:
+      2:
+        period:
+          secs: 0
+          nanos: 10000000
+        source: "Host"
+        destinations: [ "Net" ]
+mode_port: "Mode"
+ports:
+  Mode:
+    !sampling_in
+    msg_size: 20
+    refresh_period:
+      secs: 1
+      nanos: 0
+interfaces:
+  Host:
+    source: "host-in"
+    destination: "host-out"
+    mtu: 100
+    rate: 10000000
+  Net:
+    source: "net-in"
+    destination: "net-out"
+    mtu: 100
+    rate: 10000000
+"##;
+
+#[derive(Debug)]
+struct FixedTime(Duration);
+
+impl TimeSource for FixedTime {
+    fn get_time(&self) -> Result<Duration, InvalidTimeError> {
+        Ok(self.0)
+    }
+}
+
+#[derive(Debug)]
+struct ModesPartition;
+
+impl Partition<DummyHypervisor> for ModesPartition {
+    fn cold_start(&self, ctx: &mut StartContext<DummyHypervisor>) {
+        let cfg: RouterConfig<4, 4, 4, 4> = serde_yaml::from_str(CFG).unwrap();
+        let state = RouterState::create::<LoopbackNetIntf>(
+            ctx,
+            Name::from_str("Router").unwrap(),
+            cfg.interfaces.clone(),
+            cfg.ports.clone(),
+            cfg.period,
+            cfg.time_capacity,
+            cfg.stack_size,
+            entry_point,
+        )
+        .unwrap();
+        let mut router = state
+            .modal_router::<4, 4, MTU>(
+                cfg.virtual_links.clone(),
+                &cfg.scheduler,
+                &cfg.modes,
+                cfg.mode_port.as_ref(),
+                &Duration::ZERO,
+            )
+            .unwrap();
+        assert_eq!(router.active_mode(), DEFAULT_MODE);
+        assert!(router.request_mode("unknown").is_err());
+        write_sampling_port("Mode", b"unknown");
+        assert!(router.read_mode_request().is_err());
+
+        let vl = VirtualLinkId::from(1u16);
+        loopback::inject("host-in", &Frame::new(vl, b"ping")).unwrap();
+        let time = FixedTime(Duration::from_millis(20));
+        let report = router.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert_eq!(report.vl, vl);
+        assert_eq!(loopback::take("net-out"), Some((vl, b"ping".to_vec())));
+
+        // Requests only take effect at the start of a period
+        write_sampling_port("Mode", b"degraded\0\0");
+        router.read_mode_request().unwrap();
+        assert_eq!(router.active_mode(), DEFAULT_MODE);
+        let switch = Duration::from_millis(100);
+        assert!(router.apply_mode_request(&switch));
+        assert_eq!(router.active_mode(), "degraded");
+        // The port still requests the active mode
+        router.read_mode_request().unwrap();
+        assert!(!router.apply_mode_request(&switch));
+
+        // The counters of the previous mode are kept until it becomes active
+        // again.
+        let stats = router.mode_stats(DEFAULT_MODE).unwrap();
+        assert_eq!(stats.virtual_link(&vl).unwrap().messages, 1);
+
+        // The schedule of the new mode restarts at the switch, so VL 2 is not
+        // due before its period has passed twice.
+        let vl = VirtualLinkId::from(2u16);
+        loopback::inject("host-in", &Frame::new(vl, b"ping")).unwrap();
+        let time = FixedTime(switch + Duration::from_millis(15));
+        assert_eq!(router.forward::<MTU, _>(&time).unwrap(), None);
+        let stats = router.router().stats().virtual_link(&vl).unwrap();
+        assert_eq!(stats.scheduled, 0);
+
+        let time = FixedTime(switch + Duration::from_millis(20));
+        let report = router.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert_eq!(report.vl, vl);
+        assert_eq!(loopback::take("net-out"), Some((vl, b"ping".to_vec())));
+
+        write_sampling_port("Mode", DEFAULT_MODE.as_bytes());
+        router.read_mode_request().unwrap();
+        assert!(router.apply_mode_request(&(switch * 2)));
+        assert_eq!(router.active_mode(), DEFAULT_MODE);
+        let stats = router.router().stats();
+        assert_eq!(stats.virtual_link(&1u16.into()).unwrap().messages, 0);
+
+        println!("success");
+        exit(0)
+    }
+
+    fn warm_start(&self, ctx: &mut StartContext<DummyHypervisor>) {
+        self.cold_start(ctx)
+    }
+}
+
+// Not called by DummyHypervisor
+extern "C" fn entry_point() {}
+
+#[test]
+fn main() {
+    ModesPartition.run();
+}
@@ -1,7 +1,10 @@
 use a653rs::prelude::{Name, Partition, PartitionExt, StartContext};
-use a653rs_router::prelude::{RouterConfig, RouterState, VirtualLinksConfig};
+use a653rs_router::prelude::{RouterConfig, RouterState, SchedulerConfig, VirtualLinksConfig};
 use a653rs_router_tests::{test_data::CFG, DummyHypervisor, DummyNetIntf};
-use core::str::FromStr;
+use core::{
+    ptr::{addr_of, addr_of_mut},
+    str::FromStr,
+};
 use std::{process::exit, time::Duration};
 
 const MTU: usize = 1_000;
@@ -13,6 +16,7 @@ const NAME: &str = "Router";
 
 static mut ROUTER: Option<RouterState<DummyHypervisor, DummyNetIntf, INTERFACES, PORTS>> = None;
 static mut VL_CFG: Option<VirtualLinksConfig<INPUTS, OUTPUTS>> = None;
+static mut SCHEDULER_CFG: Option<SchedulerConfig> = None;
 
 #[derive(Debug)]
 struct RouterPartition;
@@ -21,7 +25,8 @@ impl Partition<DummyHypervisor> for RouterPartition {
     fn cold_start(&self, ctx: &mut StartContext<DummyHypervisor>) {
         let cfg: RouterConfig<INPUTS, OUTPUTS, INTERFACES, PORTS> =
             serde_yaml::from_str(CFG).unwrap();
-        _ = unsafe { VL_CFG.insert(cfg.virtual_links) };
+        _ = unsafe { (&mut *addr_of_mut!(VL_CFG)).insert(cfg.virtual_links) };
+        _ = unsafe { (&mut *addr_of_mut!(SCHEDULER_CFG)).insert(cfg.scheduler) };
         let router = RouterState::create::<DummyNetIntf>(
             ctx,
             Name::from_str(NAME).unwrap(),
@@ -33,9 +38,9 @@ impl Partition<DummyHypervisor> for RouterPartition {
             entry_point,
         )
         .unwrap();
-        _ = unsafe { ROUTER.insert(router) };
-        let router = unsafe { ROUTER.as_ref() }.unwrap();
-        let cfg = unsafe { VL_CFG.as_ref() }.unwrap().clone();
+        _ = unsafe { (&mut *addr_of_mut!(ROUTER)).insert(router) };
+        let router = unsafe { (&*addr_of!(ROUTER)).as_ref() }.unwrap();
+        let cfg = unsafe { (&*addr_of!(VL_CFG)).as_ref() }.unwrap().clone();
         println!("router = {router:?}, cfg = {cfg:?}");
         router.start().unwrap();
 
@@ -51,10 +56,11 @@ impl Partition<DummyHypervisor> for RouterPartition {
 
 // Not called by DummyHypervisor
 extern "C" fn entry_point() {
-    let router = unsafe { ROUTER.as_ref() }.unwrap();
-    let cfg = unsafe { VL_CFG.as_ref() }.unwrap().clone();
+    let router = unsafe { (&*addr_of!(ROUTER)).as_ref() }.unwrap();
+    let cfg = unsafe { (&*addr_of!(VL_CFG)).as_ref() }.unwrap().clone();
+    let scheduler_cfg = unsafe { (&*addr_of!(SCHEDULER_CFG)).as_ref() }.unwrap();
     let router = router
-        .router::<INPUTS, OUTPUTS, MTU>(cfg, &Duration::from_secs(0))
+        .router::<INPUTS, OUTPUTS, MTU>(cfg, scheduler_cfg, &Duration::from_secs(0))
         .unwrap();
     println!("{router:?}")
 }
@@ -0,0 +1,179 @@
+use a653rs::prelude::{Name, Partition, PartitionExt, StartContext};
+use a653rs_router::prelude::{
+    Frame, InvalidTimeError, RouterConfig, RouterState, TimeSource, VirtualLinkId,
+};
+use a653rs_router_tests::{
+    loopback::{self, LoopbackNetIntf},
+    DummyHypervisor,
+};
+use core::str::FromStr;
+use std::{process::exit, time::Duration};
+
+const MTU: usize = 100;
+
+const NODE_A: &str = r##"
+period:
+  secs: 1
+  nanos: 0
+time_capacity:
+  secs: 0
+  nanos: 300000
+stack_size: 10000
+virtual_links:
+  1:
+    period:
+      secs: 0
+      nanos: 10000
+    source: "Host"
+    destinations: [ "NetA" ]
+    redundancy:
+      a: "NetA"
+      b: "NetB"
+      skew_max:
+        secs: 0
+        nanos: 1000000
+interfaces:
+  Host:
+    source: "a-in"
+    destination: "a-out"
+    mtu: 100
+    rate: 10000000
+  NetA:
+    source: "b-a.a"
+    destination: "a-b.a"
+    mtu: 100
+    rate: 10000000
+  NetB:
+    source: "b-a.b"
+    destination: "a-b.b"
+    mtu: 100
+    rate: 10000000
+"##;
+
+const NODE_B: &str = r##"
+period:
+  secs: 1
+  nanos: 0
+time_capacity:
+  secs: 0
+  nanos: 300000
+stack_size: 10000
+virtual_links:
+  1:
+    period:
+      secs: 0
+      nanos: 10000
+    source: "NetA"
+    destinations: [ "Host" ]
+    redundancy:
+      a: "NetA"
+      b: "NetB"
+      skew_max:
+        secs: 0
+        nanos: 1000000
+interfaces:
+  Host:
+    source: "b-in"
+    destination: "b-out"
+    mtu: 100
+    rate: 0
+  NetA:
+    source: "a-b.a"
+    destination: "b-a.a"
+    mtu: 100
+    rate: 10000000
+  NetB:
+    source: "a-b.b"
+    destination: "b-a.b"
+    mtu: 100
+    rate: 10000000
+"##;
+
+#[derive(Debug)]
+struct FixedTime(Duration);
+
+impl TimeSource for FixedTime {
+    fn get_time(&self) -> Result<Duration, InvalidTimeError> {
+        Ok(self.0)
+    }
+}
+
+#[derive(Debug)]
+struct RedundancyPartition;
+
+impl Partition<DummyHypervisor> for RedundancyPartition {
+    fn cold_start(&self, ctx: &mut StartContext<DummyHypervisor>) {
+        let node_a: RouterConfig<4, 4, 4, 4> = serde_yaml::from_str(NODE_A).unwrap();
+        let node_b: RouterConfig<4, 4, 4, 4> = serde_yaml::from_str(NODE_B).unwrap();
+        let state_a = create(ctx, "NodeA", &node_a);
+        let state_b = create(ctx, "NodeB", &node_b);
+        let start = Duration::ZERO;
+        let mut router_a = state_a
+            .router::<4, 4, MTU>(node_a.virtual_links, &node_a.scheduler, &start)
+            .unwrap();
+        let mut router_b = state_b
+            .router::<4, 4, MTU>(node_b.virtual_links, &node_b.scheduler, &start)
+            .unwrap();
+        let vl = VirtualLinkId::from(1u16);
+
+        // Messages are sent on both networks and only the first copy is
+        // forwarded
+        loopback::inject("a-in", &Frame::new(vl, b"ping")).unwrap();
+        let time = FixedTime(Duration::from_millis(1));
+        let report = router_a.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.destinations.len(), 2);
+        let report = router_b.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert_eq!(report.messages, 1);
+        assert_eq!(loopback::take("b-out"), Some((vl, b"ping".to_vec())));
+        assert_eq!(loopback::take("b-out"), None);
+
+        // Messages lost on one network are received from the other network
+        loopback::inject("a-in", &Frame::new(vl, b"pong")).unwrap();
+        let time = FixedTime(Duration::from_millis(2));
+        let report = router_a.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert!(report.is_ok());
+        assert!(loopback::take("a-b.a").is_some());
+        let report = router_b.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert_eq!(report.messages, 1);
+        assert_eq!(loopback::take("b-out"), Some((vl, b"pong".to_vec())));
+
+        let stats = router_b.stats().virtual_link(&vl).unwrap();
+        assert_eq!(stats.messages, 2);
+        assert_eq!(stats.redundant, 1);
+        assert_eq!(stats.lost, 0);
+
+        println!("success");
+        exit(0)
+    }
+
+    fn warm_start(&self, ctx: &mut StartContext<DummyHypervisor>) {
+        self.cold_start(ctx)
+    }
+}
+
+fn create(
+    ctx: &mut StartContext<DummyHypervisor>,
+    name: &str,
+    cfg: &RouterConfig<4, 4, 4, 4>,
+) -> RouterState<DummyHypervisor, LoopbackNetIntf, 4, 4> {
+    RouterState::create::<LoopbackNetIntf>(
+        ctx,
+        Name::from_str(name).unwrap(),
+        cfg.interfaces.clone(),
+        cfg.ports.clone(),
+        cfg.period,
+        cfg.time_capacity,
+        cfg.stack_size,
+        entry_point,
+    )
+    .unwrap()
+}
+
+// Not called by DummyHypervisor
+extern "C" fn entry_point() {}
+
+#[test]
+fn main() {
+    RedundancyPartition.run();
+}
@@ -0,0 +1,118 @@
+use a653rs::prelude::{Name, Partition, PartitionExt, StartContext};
+use a653rs_router::prelude::{
+    Frame, InvalidTimeError, RouterConfig, RouterState, Scheduler, TimeSource, VirtualLinkId,
+};
+use a653rs_router_tests::{
+    loopback::{self, LoopbackNetIntf},
+    DummyHypervisor,
+};
+use core::str::FromStr;
+use std::{collections::VecDeque, process::exit, time::Duration};
+
+const MTU: usize = 100;
+
+const CFG: &str = r##"
+period:
+  secs: 0
+  nanos: 10000000
+time_capacity:
+  secs: 0
+  nanos: 300000
+stack_size: 10000
+virtual_links:
+  1:
+    period:
+      secs: 0
+      nanos: 10000000
+    source: "Host"
+    destinations: [ "Net" ]
+interfaces:
+  Host:
+    source: "host-in"
+    destination: "host-out"
+    mtu: 100
+    rate: 10000000
+  Net:
+    source: "net-in"
+    destination: "net-out"
+    mtu: 100
+    rate: 10000000
+"##;
+
+#[derive(Debug)]
+struct FixedTime(Duration);
+
+impl TimeSource for FixedTime {
+    fn get_time(&self) -> Result<Duration, InvalidTimeError> {
+        Ok(self.0)
+    }
+}
+
+/// Schedules the virtual links in the given order, regardless of the time.
+#[derive(Debug)]
+struct Scripted(VecDeque<Option<VirtualLinkId>>);
+
+impl Scheduler for Scripted {
+    fn schedule_next(&mut self, _current_time: &Duration) -> Option<VirtualLinkId> {
+        self.0.pop_front().flatten()
+    }
+}
+
+#[derive(Debug)]
+struct SchedulerPartition;
+
+impl Partition<DummyHypervisor> for SchedulerPartition {
+    fn cold_start(&self, ctx: &mut StartContext<DummyHypervisor>) {
+        let cfg: RouterConfig<4, 4, 4, 4> = serde_yaml::from_str(CFG).unwrap();
+        let state = RouterState::create::<LoopbackNetIntf>(
+            ctx,
+            Name::from_str("Router").unwrap(),
+            cfg.interfaces.clone(),
+            cfg.ports.clone(),
+            cfg.period,
+            cfg.time_capacity,
+            cfg.stack_size,
+            entry_point,
+        )
+        .unwrap();
+        let vl = VirtualLinkId::from(1u16);
+        let scheduler = Scripted([None, Some(vl)].into());
+        let mut router = state
+            .router_with_scheduler::<4, 4, MTU, _>(cfg.virtual_links.clone(), scheduler)
+            .unwrap();
+        loopback::inject("host-in", &Frame::new(vl, b"ping")).unwrap();
+
+        // Nothing is forwarded while the custom scheduler holds back the VL
+        let time = FixedTime(Duration::from_millis(1));
+        assert_eq!(router.forward::<MTU, _>(&time).unwrap(), None);
+        assert_eq!(loopback::take("net-out"), None);
+
+        let time = FixedTime(Duration::from_millis(2));
+        let report = router.forward::<MTU, _>(&time).unwrap().unwrap();
+        assert_eq!(report.vl, vl);
+        assert!(report.is_ok());
+        assert_eq!(loopback::take("net-out"), Some((vl, b"ping".to_vec())));
+        let stats = router.stats().virtual_link(&vl).unwrap();
+        assert_eq!(stats.scheduled, 1);
+        assert_eq!(stats.messages, 1);
+
+        // The script has ended
+        let time = FixedTime(Duration::from_millis(3));
+        assert_eq!(router.forward::<MTU, _>(&time).unwrap(), None);
+
+        println!("success");
+        exit(0)
+    }
+
+    fn warm_start(&self, ctx: &mut StartContext<DummyHypervisor>) {
+        self.cold_start(ctx)
+    }
+}
+
+// Not called by DummyHypervisor
+extern "C" fn entry_point() {}
+
+#[test]
+fn main() {
+    SchedulerPartition.run();
+}
@@ -6,5 +6,8 @@
 mod macros;
 
 mod network;
+mod registers;
+#[cfg(test)]
+mod sim;
 
-pub use network::UartNetworkInterface;
+pub use network::{UartNetworkInterface, UartStats, MAX_MTU};
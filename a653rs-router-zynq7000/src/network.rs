@@ -1,99 +1,135 @@
+use crate::registers::{lsr, Uart16550};
 use a653rs_router::prelude::{
-    CreateNetworkInterfaceId, InterfaceConfig, InterfaceError, NetworkInterfaceId,
-    PlatformNetworkInterface,
+    CreateNetworkInterfaceId, Frame, InterfaceConfig, InterfaceError, NetworkInterfaceId,
+    PlatformNetworkInterface, UartFrame,
 };
-use cobs::{decode_in_place, encode};
-use core::mem::size_of;
-use heapless::spsc::Queue;
-use once_cell::unsync::Lazy;
-use uart_xilinx::MmioUartAxi16550;
+use core::ptr::{addr_of, addr_of_mut};
+use heapless::{spsc::Queue, Vec};
+
+#[cfg(not(test))]
+type Uart = uart_xilinx::MmioUartAxi16550<'static>;
+
+#[cfg(test)]
+type Uart = crate::sim::SimulatedUart;
 
 /// Networking on XNG.
+///
+/// Each interface uses its own AXI 16550 UART, which is configured using the
+/// `source` and `destination` of its [`InterfaceConfig`]:
+/// - `source` is the base address of the UART, optionally followed by a slash
+///   and the clock rate in Hz, e.g. `0x43C00000/50000000`.
+/// - `destination` is the baud rate, e.g. `115200`.
+///
+/// Defaults are used for empty values.
+///
+/// Sending a frame does not wait for the UART. Encoded frames are buffered and
+/// written to the TX FIFO whenever it is empty, which is checked on each send
+/// and receive of the interface and by [`UartNetworkInterface::poll`].
+///
+/// The frame buffers are sized for an `MTU` of up to [`MAX_MTU`]. The MTU of
+/// the [`InterfaceConfig`] must not exceed `MTU`.
 #[derive(Debug)]
 pub struct UartNetworkInterface<const MTU: usize>;
 
+impl<const MTU: usize> UartNetworkInterface<MTU> {
+    /// Continues transmitting the buffered frames of all UARTs.
+    ///
+    /// Should be called after each forwarded virtual link, so frames are sent
+    /// even if the interface is not used by the next virtual links.
+    pub fn poll() {
+        // This is safe, because the UARTs are never accessed concurrently.
+        for uart in unsafe { (&mut *addr_of_mut!(UARTS)).iter_mut() } {
+            uart.drain();
+        }
+    }
+
+    /// Receive counters of the interface.
+    pub fn stats(id: NetworkInterfaceId) -> UartStats {
+        get_uart(id).map_or(UartStats::default(), |u| u.stats)
+    }
+}
+
+/// Largest MTU of a [`UartNetworkInterface`].
+pub const MAX_MTU: usize = 2_000;
+
 mod config {
+    use a653rs_router::prelude::UartFrame;
+
     pub const BASE_ADDRESS: usize = 0x43C0_0000;
     pub const CLOCK_RATE: usize = 50_000_000;
     pub const BAUD_RATE: usize = 115200;
     pub const FIFO_DEPTH: usize = 16;
-    pub const FRAME_BUFFER: usize = 20000;
+    /// Bytes of a partially received frame.
+    pub const RX_BUFFER: usize = UartFrame::<{ super::MAX_MTU }>::MAX_ENCODED_LEN;
+    /// Bytes of the encoded frames that are not yet written to the TX FIFO.
+    pub const TX_BUFFER: usize = 4 * RX_BUFFER;
+    pub const MAX_UARTS: usize = 4;
 }
 
-struct UartFrame<'p, const MTU: usize> {
-    pl: &'p [u8],
+/// Line status errors of received bytes.
+const RX_ERRORS: u8 = lsr::PARITY_ERROR | lsr::FRAMING_ERROR | lsr::BREAK_INTERRUPT;
+
+/// Receive counters of a [`UartNetworkInterface`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UartStats {
+    /// Frames that were received.
+    pub received: usize,
+    /// Times received bytes were lost, because the RX FIFO was full.
+    pub overrun_errors: usize,
+    /// Bytes that were received with a wrong parity bit.
+    pub parity_errors: usize,
+    /// Bytes that were received without a valid stop bit, including breaks.
+    pub framing_errors: usize,
+    /// Frames that were discarded, because they were longer than the largest
+    /// encoded frame.
+    pub oversized: usize,
+    /// Frames that were discarded, because they were not valid COBS or their
+    /// CRC did not match.
+    pub invalid: usize,
 }
 
-/// encoded: COBS(vl_id + payload + CRC16)
-impl<'p, const MTU: usize> UartFrame<'p, MTU> {
-    const fn max_decoded_len() -> usize {
-        core::mem::size_of::<u16>() + MTU + core::mem::size_of::<u16>()
-    }
-
-    const fn max_encoded_len() -> usize {
-        max_encoded_len(2 * core::mem::size_of::<u16>() + MTU) + 1
-    }
-
-    fn frame_encoded_len(&self) -> usize {
-        max_encoded_len(core::mem::size_of::<u16>() + self.pl.len() + core::mem::size_of::<u16>())
-    }
-
-    /// Encodes the frame contents, excluding the
-    fn encode<'a>(&self, encoded: &'a mut [u8]) -> Result<&'a [u8], ()>
-    where
-        [(); Self::max_decoded_len()]:,
-    {
-        let mut buf = [0u8; Self::max_decoded_len()];
-        if self.pl.len() > MTU || self.frame_encoded_len() > encoded.len() {
-            return Err(());
-        }
-
-        // Payload
-        buf[..self.pl.len()].copy_from_slice(self.pl);
-
-        // CRC
-        let crc = crc16::State::<crc16::USB>::calculate(&buf[..self.pl.len()]);
-        let crc: [u8; 2] = crc.to_be_bytes();
-        buf[self.pl.len()..self.pl.len() + 2].copy_from_slice(&crc);
-
-        // COBS encode
-        let enclen = encode(&buf[0..self.pl.len() + 2], encoded);
-
-        Ok(&encoded[..enclen])
-    }
-
-    fn decode(buf: &mut [u8]) -> Result<&[u8], ()> {
-        // COBS decode
-        let declen = decode_in_place(buf).or(Err(()))?;
-
-        let crclen = size_of::<u16>();
-        if declen < crclen {
-            return Err(());
-        }
+/// Frame buffers of a [`BufferedUart`].
+///
+/// These are kept apart from the UART, so they can be placed in a static
+/// instead of being moved there from the stack.
+struct FrameBuffers<const RX_LEN: usize, const TX_LEN: usize> {
+    /// Bytes of the partially received frame.
+    rx_frame: Vec<u8, RX_LEN>,
+    /// Encoded frames that are not yet written to the TX FIFO.
+    tx: Queue<u8, TX_LEN>,
+}
 
-        // Check CRC
-        let (msg, crc) = buf.split_at(declen - crclen);
-        let crc = crc[0..2].try_into().or(Err(()))?;
-        let rcrc = u16::from_be_bytes(crc);
-        let crc = crc16::State::<crc16::USB>::calculate(msg);
-        if rcrc != crc {
-            return Err(());
+impl<const RX_LEN: usize, const TX_LEN: usize> FrameBuffers<RX_LEN, TX_LEN> {
+    const fn new() -> Self {
+        Self {
+            rx_frame: Vec::new(),
+            tx: Queue::new(),
         }
-
-        Ok(msg)
     }
 }
 
-struct BufferedUart<const BUFFER_LEN: usize> {
-    uart: MmioUartAxi16550<'static>,
-    rx_buffer: Queue<u8, BUFFER_LEN>,
+struct BufferedUart<'buf, U: Uart16550, const RX_LEN: usize, const TX_LEN: usize> {
+    base_address: usize,
+    uart: U,
+    buffers: &'buf mut FrameBuffers<RX_LEN, TX_LEN>,
+    /// Whether bytes are discarded until the end of the current frame.
+    resync: bool,
+    /// Line status errors of the byte at the head of the RX FIFO.
+    rx_errors: u8,
+    stats: UartStats,
 }
 
-impl<const BUFFER_LEN: usize> BufferedUart<BUFFER_LEN> {
-    fn new(base_address: usize) -> Self {
+impl<'buf, U: Uart16550, const RX_LEN: usize, const TX_LEN: usize>
+    BufferedUart<'buf, U, RX_LEN, TX_LEN>
+{
+    fn new(base_address: usize, buffers: &'buf mut FrameBuffers<RX_LEN, TX_LEN>) -> Self {
         BufferedUart {
-            uart: MmioUartAxi16550::new(base_address),
-            rx_buffer: Queue::new(),
+            base_address,
+            uart: U::new(base_address),
+            buffers,
+            resync: false,
+            rx_errors: 0,
+            stats: UartStats::default(),
         }
     }
 
@@ -128,9 +164,135 @@ impl<const BUFFER_LEN: usize> BufferedUart<BUFFER_LEN> {
         // Rx FIFO trigger level is 1 byte, reset Rx & Tx FIFO, enable FIFO
         self.uart.write_fcr(0b00_000_11_1);
     }
+
+    fn is_data_ready(&mut self) -> bool {
+        self.line_status() & lsr::DATA_READY != 0
+    }
+
+    /// Moves bytes from the TX buffer to the TX FIFO, if the TX FIFO is empty.
+    /// Does not wait for the UART.
+    fn drain(&mut self) {
+        if self.buffers.tx.is_empty() || !self.uart.is_transmitter_holding_register_empty() {
+            return;
+        }
+        for _ in 0..config::FIFO_DEPTH {
+            match self.buffers.tx.dequeue() {
+                Some(b) => self.uart.write_byte(b),
+                None => break,
+            }
+        }
+    }
+
+    /// Reads the line status and accounts for lost bytes.
+    ///
+    /// Errors of the byte at the head of the RX FIFO are kept until the byte is
+    /// read, because reading the line status clears them.
+    fn line_status(&mut self) -> u8 {
+        let status = self.uart.read_lsr() | self.rx_errors;
+        if status & lsr::OVERRUN_ERROR != 0 {
+            self.stats.overrun_errors += 1;
+            self.discard();
+        }
+        self.rx_errors = status & RX_ERRORS;
+        status
+    }
+
+    /// Discards the partially received frame and the following bytes up to the
+    /// start of the next frame.
+    fn discard(&mut self) {
+        self.buffers.rx_frame.clear();
+        self.resync = true;
+    }
+
+    fn receive<'a, const MTU: usize>(
+        &mut self,
+        buffer: &'a mut [u8],
+    ) -> Result<Frame<'a>, InterfaceError> {
+        while self.line_status() & lsr::DATA_READY != 0 {
+            let errors = core::mem::take(&mut self.rx_errors);
+            let Some(b) = self.uart.read_byte() else {
+                break;
+            };
+            if errors & lsr::PARITY_ERROR != 0 {
+                self.stats.parity_errors += 1;
+            }
+            if errors & (lsr::FRAMING_ERROR | lsr::BREAK_INTERRUPT) != 0 {
+                self.stats.framing_errors += 1;
+            }
+            if errors != 0 {
+                self.discard();
+            }
+            if b == UartFrame::<MTU>::TERMINATOR {
+                if core::mem::take(&mut self.resync) || self.buffers.rx_frame.is_empty() {
+                    continue;
+                }
+                return self.decode::<MTU>(buffer);
+            }
+            if self.resync {
+                continue;
+            }
+            // The terminator is not part of the buffered frame.
+            if self.buffers.rx_frame.len() + 1 >= UartFrame::<MTU>::MAX_ENCODED_LEN
+                || self.buffers.rx_frame.push(b).is_err()
+            {
+                self.stats.oversized += 1;
+                self.discard();
+            }
+        }
+        Err(InterfaceError::NoData)
+    }
+
+    fn decode<'a, const MTU: usize>(
+        &mut self,
+        buffer: &'a mut [u8],
+    ) -> Result<Frame<'a>, InterfaceError> {
+        let res = match UartFrame::<MTU>::decode(&mut self.buffers.rx_frame) {
+            Ok(frame) => buffer
+                .get_mut(..frame.payload.len())
+                .map(|msg| {
+                    msg.copy_from_slice(frame.payload);
+                    Frame {
+                        payload: msg,
+                        ..frame
+                    }
+                })
+                .ok_or(InterfaceError::InsufficientBuffer),
+            Err(e) => {
+                self.stats.invalid += 1;
+                Err(e)
+            }
+        };
+        if res.is_ok() {
+            self.stats.received += 1;
+        }
+        self.buffers.rx_frame.clear();
+        res
+    }
+
+    fn send<const MTU: usize>(&mut self, frame: &Frame<'_>) -> Result<usize, InterfaceError>
+    where
+        [(); UartFrame::<MTU>::MAX_ENCODED_LEN]:,
+    {
+        let mut buf = [0u8; UartFrame::<MTU>::MAX_ENCODED_LEN];
+        let encoded = UartFrame::<MTU>::encode(frame, &mut buf)?;
+
+        self.drain();
+        if self.buffers.tx.capacity() - self.buffers.tx.len() < encoded.len() {
+            return Err(InterfaceError::InsufficientBuffer);
+        }
+        for b in encoded {
+            // This can not fail, because there is enough space left.
+            _ = self.buffers.tx.enqueue(*b);
+        }
+        self.drain();
+
+        Ok(encoded.len())
+    }
 }
 
-impl<const BUFFER_LEN: usize> Drop for BufferedUart<BUFFER_LEN> {
+impl<U: Uart16550, const RX_LEN: usize, const TX_LEN: usize> Drop
+    for BufferedUart<'_, U, RX_LEN, TX_LEN>
+{
     fn drop(&mut self) {
         self.uart.write_ier(0);
         _ = self.uart.read_msr();
@@ -141,128 +303,304 @@ impl<const BUFFER_LEN: usize> Drop for BufferedUart<BUFFER_LEN> {
     }
 }
 
-static mut UART: Lazy<BufferedUart<{ config::FRAME_BUFFER }>> = Lazy::new(|| {
-    let mut b = BufferedUart::new(config::BASE_ADDRESS);
-    b.init(config::CLOCK_RATE, config::BAUD_RATE);
-    b
-});
+type StaticUart = BufferedUart<'static, Uart, { config::RX_BUFFER }, { config::TX_BUFFER }>;
+
+static mut BUFFERS: [FrameBuffers<{ config::RX_BUFFER }, { config::TX_BUFFER }>;
+    config::MAX_UARTS] = [const { FrameBuffers::new() }; config::MAX_UARTS];
+
+static mut UARTS: Vec<StaticUart, { config::MAX_UARTS }> = Vec::new();
+
+/// This is only safe, because the UARTs are only used *after* they are all
+/// created and they are never accessed concurrently.
+fn get_uart(id: NetworkInterfaceId) -> Result<&'static mut StaticUart, InterfaceError> {
+    unsafe {
+        (&mut *addr_of_mut!(UARTS))
+            .get_mut(id.0 as usize)
+            .ok_or(InterfaceError::NotFound)
+    }
+}
+
+/// Base address, clock rate and baud rate of a UART.
+fn parse_config(cfg: &InterfaceConfig) -> Result<(usize, usize, usize), InterfaceError> {
+    let (address, clock) = cfg.source.split_once('/').unwrap_or((&cfg.source, ""));
+    let address = match address.trim() {
+        "" => config::BASE_ADDRESS,
+        a => {
+            let hex = a.strip_prefix("0x").ok_or(InterfaceError::InvalidData)?;
+            usize::from_str_radix(hex, 16).or(Err(InterfaceError::InvalidData))?
+        }
+    };
+    let parse = |s: &str, default| match s.trim() {
+        "" => Ok(default),
+        s => s.parse().or(Err(InterfaceError::InvalidData)),
+    };
+    Ok((
+        address,
+        parse(clock, config::CLOCK_RATE)?,
+        parse(&cfg.destination, config::BAUD_RATE)?,
+    ))
+}
 
 impl<const MTU: usize> PlatformNetworkInterface for UartNetworkInterface<MTU>
 where
-    [(); UartFrame::<MTU>::max_encoded_len()]:,
-    [(); UartFrame::<MTU>::max_decoded_len()]:,
+    [(); UartFrame::<MTU>::MAX_ENCODED_LEN]:,
 {
     fn platform_interface_receive_unchecked(
         id: NetworkInterfaceId,
         buffer: &'_ mut [u8],
-    ) -> Result<&'_ [u8], InterfaceError> {
-        if unsafe { !UART.uart.is_data_ready() } {
+    ) -> Result<Frame<'_>, InterfaceError> {
+        let uart = get_uart(id)?;
+        uart.drain();
+        if !uart.is_data_ready() {
             return Err(InterfaceError::NoData);
         }
         trace!(begin_network_receive, id.0 as u16);
-        let mut limit = 0;
-        let mut queue_has_eof = false;
-        while limit < u8::MAX && !queue_has_eof {
-            limit += 1;
-            while let Some(b) = unsafe { UART.uart.read_byte() } {
-                _ = unsafe { UART.rx_buffer.enqueue(b) };
-                if b == 0x0 {
-                    queue_has_eof = true;
-                    break;
-                }
-            }
-        }
-        if !queue_has_eof {
-            trace!(end_network_receive, id.0 as u16);
-            return Err(InterfaceError::NoData);
-        }
-        let mut buf = [0u8; UartFrame::<MTU>::max_encoded_len()];
-        for b in buf.iter_mut() {
-            if let Some(c) = unsafe { UART.rx_buffer.dequeue() } {
-                *b = c;
-                if c == 0x0 {
-                    break;
-                }
-            } else {
-                break;
-            }
-        }
-        match UartFrame::<MTU>::decode(&mut buf) {
-            Ok(pl) => {
-                let rpl = &mut buffer[0..pl.len()];
-                rpl.copy_from_slice(pl);
-                trace!(end_network_receive, id.0 as u16);
-                Ok(rpl)
-            }
-            _ => {
-                trace!(end_network_receive, id.0 as u16);
-                Err(InterfaceError::InvalidData)
-            }
-        }
+        let res = uart.receive::<MTU>(buffer);
+        trace!(end_network_receive, id.0 as u16);
+        res
     }
 
     fn platform_interface_send_unchecked(
         id: NetworkInterfaceId,
-        buffer: &[u8],
+        frame: &Frame<'_>,
     ) -> Result<usize, InterfaceError> {
-        let mut buf = [0u8; UartFrame::<MTU>::max_encoded_len()];
-        let frame = UartFrame::<MTU> { pl: buffer };
-
-        let encoded =
-            UartFrame::<MTU>::encode(&frame, &mut buf).or(Err(InterfaceError::InvalidData))?;
-
+        let uart = get_uart(id)?;
         trace!(begin_network_send, id.0 as u16);
-        unsafe {
-            let mut index: usize = 0;
-            while index < encoded.len() {
-                while !UART.uart.is_transmitter_holding_register_empty() {}
-                for _ in 0..config::FIFO_DEPTH {
-                    UART.uart.write_byte(encoded[index]);
-                    index += 1;
-                    if index == encoded.len() {
-                        break;
-                    }
-                }
-            }
-
-            // Wait for transmission to finish
-            while !UART.uart.is_transmitter_holding_register_empty() {}
-        }
+        let res = uart.send::<MTU>(frame);
         trace!(end_network_send, id.0 as u16);
-
-        Ok(encoded.len())
-    }
-}
-
-static mut ID: usize = 0;
-
-fn next_id() -> NetworkInterfaceId {
-    unsafe {
-        ID += 1;
-        NetworkInterfaceId::from(ID)
+        res
     }
 }
 
 impl<const MTU: usize> CreateNetworkInterfaceId<UartNetworkInterface<MTU>>
     for UartNetworkInterface<MTU>
 where
-    [(); UartFrame::<MTU>::max_encoded_len()]:,
-    [(); UartFrame::<MTU>::max_decoded_len()]:,
+    [(); UartFrame::<MTU>::MAX_ENCODED_LEN]:,
 {
     fn create_network_interface_id(
-        _cfg: &InterfaceConfig,
+        cfg: &InterfaceConfig,
     ) -> Result<NetworkInterfaceId, InterfaceError> {
-        Ok(next_id())
+        // Frames with a payload of up to the MTU of the configuration have to
+        // fit into the buffers, which are sized for a payload of `MTU`.
+        let decoded = Frame::MAX_HEADER_LEN + cfg.mtu + UartFrame::<MTU>::CRC_LEN;
+        if MTU > MAX_MTU || decoded > UartFrame::<MTU>::MAX_DECODED_LEN {
+            return Err(InterfaceError::InsufficientBuffer);
+        }
+        let (address, clock_rate, baud_rate) = parse_config(cfg)?;
+        // This is safe, because the UARTs are only created before they are used.
+        unsafe {
+            if (&*addr_of!(UARTS))
+                .iter()
+                .any(|u| u.base_address == address)
+            {
+                return Err(InterfaceError::InvalidData);
+            }
+            let id = NetworkInterfaceId::from((&*addr_of!(UARTS)).len());
+            let buffers = (&mut *addr_of_mut!(BUFFERS))
+                .get_mut(id.0 as usize)
+                .ok_or(InterfaceError::InsufficientBuffer)?;
+            (&mut *addr_of_mut!(UARTS))
+                .push(BufferedUart::new(address, buffers))
+                .or(Err(InterfaceError::InsufficientBuffer))?;
+            (&mut *addr_of_mut!(UARTS))[id.0 as usize].init(clock_rate, baud_rate);
+            Ok(id)
+        }
     }
 }
 
-const fn max_encoded_len(raw_len: usize) -> usize {
-    let overhead = if raw_len == 0 {
-        // Just 0xff
-        1
-    } else {
-        (raw_len + 253) / 254
-    };
-    // +1 for terminator byte.
-    raw_len + overhead + 1
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{registers::lsr, sim::SimulatedUart};
+    use a653rs_router::prelude::{DataRate, VirtualLinkId};
+
+    const MTU: usize = 16;
+
+    type Intf = UartNetworkInterface<MTU>;
+
+    const RX_LEN: usize = UartFrame::<MTU>::MAX_ENCODED_LEN;
+
+    const TX_LEN: usize = 64;
+
+    type Buffers = FrameBuffers<RX_LEN, TX_LEN>;
+
+    type TestUart<'a> = BufferedUart<'a, SimulatedUart, RX_LEN, TX_LEN>;
+
+    const PAYLOAD: &[u8] = &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1];
+
+    fn frame() -> Frame<'static> {
+        Frame::new(VirtualLinkId::from(7u16), PAYLOAD)
+    }
+
+    fn encoded(buf: &mut [u8; RX_LEN]) -> &[u8] {
+        let encoded = UartFrame::<MTU>::encode(&frame(), buf).unwrap();
+        assert!(encoded.len() > config::FIFO_DEPTH);
+        encoded
+    }
+
+    /// Feeds `bytes` to the UART without overrunning its RX FIFO and returns
+    /// the payload length of the first received frame.
+    fn receive<const RX: usize, const TX: usize>(
+        uart: &mut BufferedUart<'_, SimulatedUart, RX, TX>,
+        bytes: &[u8],
+    ) -> Result<usize, InterfaceError> {
+        let mut buf = [0u8; MTU];
+        for chunk in bytes.chunks(config::FIFO_DEPTH) {
+            uart.uart.feed(chunk);
+            match uart.receive::<MTU>(&mut buf) {
+                Err(InterfaceError::NoData) => {}
+                res => return res.map(|f| f.payload.len()),
+            }
+        }
+        Err(InterfaceError::NoData)
+    }
+
+    #[test]
+    fn uart_config_is_parsed() {
+        let cfg = |src: &str, dst: &str| InterfaceConfig::new(src, dst, DataRate::b(0), MTU);
+        assert_eq!(
+            parse_config(&cfg("0x43C10000/100000000", "9600")),
+            Ok((0x43C1_0000, 100_000_000, 9600))
+        );
+        assert_eq!(
+            parse_config(&cfg("", "")),
+            Ok((config::BASE_ADDRESS, config::CLOCK_RATE, config::BAUD_RATE))
+        );
+        assert_eq!(
+            parse_config(&cfg("43C10000", "")),
+            Err(InterfaceError::InvalidData)
+        );
+        assert_eq!(
+            parse_config(&cfg("0x43C10000/fast", "")),
+            Err(InterfaceError::InvalidData)
+        );
+
+        // This is the only test that creates interfaces in the global UARTS
+        let id = Intf::create_network_interface_id(&cfg("0x43C10000/100000000", "9600")).unwrap();
+        let uart = &get_uart(id).unwrap().uart;
+        assert_eq!(uart.base_address, 0x43C1_0000);
+        assert_eq!(uart.baud_rate, 9600);
+        assert_eq!(
+            Intf::create_network_interface_id(&cfg("0x43C10000", "")),
+            Err(InterfaceError::InvalidData)
+        );
+        assert_eq!(
+            UartNetworkInterface::<{ MAX_MTU + 1 }>::create_network_interface_id(&cfg(
+                "0x43C20000",
+                ""
+            )),
+            Err(InterfaceError::InsufficientBuffer)
+        );
+        assert_eq!(
+            Intf::create_network_interface_id(&InterfaceConfig::new(
+                "0x43C20000",
+                "",
+                DataRate::b(0),
+                MTU + 1
+            )),
+            Err(InterfaceError::InsufficientBuffer)
+        );
+    }
+
+    #[test]
+    fn uart_send_does_not_wait_for_full_tx_fifo() {
+        let mut buffers = Buffers::new();
+        let mut uart = TestUart::new(0, &mut buffers);
+        let mut buf = [0u8; UartFrame::<MTU>::MAX_ENCODED_LEN];
+        let encoded = encoded(&mut buf);
+
+        // Sending only fills the TX FIFO and the rest is sent while polling
+        assert_eq!(uart.send::<MTU>(&frame()), Ok(encoded.len()));
+        assert!(uart.uart.line.is_empty());
+        uart.uart.flush();
+        assert_eq!(uart.uart.line, encoded[..config::FIFO_DEPTH]);
+        uart.drain();
+        uart.uart.flush();
+        assert_eq!(uart.uart.line, encoded);
+
+        // Frames are rejected if the TX buffer is full, while the TX FIFO is
+        // never overrun
+        let res = (0..TX_LEN)
+            .map(|_| uart.send::<MTU>(&frame()))
+            .find(Result::is_err);
+        assert_eq!(res, Some(Err(InterfaceError::InsufficientBuffer)));
+        assert_eq!(uart.uart.tx_overruns, 0);
+    }
+
+    #[test]
+    fn uart_resyncs_after_corrupt_bytes() {
+        let mut buffers = Buffers::new();
+        let mut uart = TestUart::new(0, &mut buffers);
+        let mut buf = [0u8; UartFrame::<MTU>::MAX_ENCODED_LEN];
+        let encoded = encoded(&mut buf);
+        let mut rx = [0u8; MTU];
+
+        // Partial frames are buffered until the rest of the frame arrives
+        let (head, tail) = encoded.split_at(encoded.len() / 2);
+        uart.uart.feed(head);
+        assert_eq!(uart.receive::<MTU>(&mut rx), Err(InterfaceError::NoData));
+        uart.uart.feed(tail);
+        assert_eq!(uart.receive::<MTU>(&mut rx), Ok(frame()));
+
+        // Lost bytes are detected by the CRC
+        let mut lossy = Vec::<u8, 32>::from_slice(encoded).unwrap();
+        _ = lossy.remove(3);
+        assert_eq!(receive(&mut uart, &lossy), Err(InterfaceError::InvalidData));
+
+        // Frames with line errors are discarded
+        for error in [lsr::PARITY_ERROR, lsr::FRAMING_ERROR] {
+            uart.uart.feed_with_errors(&encoded[..1], error);
+            assert_eq!(uart.receive::<MTU>(&mut rx), Err(InterfaceError::NoData));
+            assert_eq!(
+                receive(&mut uart, &encoded[1..]),
+                Err(InterfaceError::NoData)
+            );
+            assert_eq!(receive(&mut uart, encoded), Ok(PAYLOAD.len()));
+        }
+
+        // Frames without terminator are discarded once they are too long and
+        // the receiver skips to the start of the next frame
+        let garbage = [0xAA; RX_LEN];
+        assert_eq!(receive(&mut uart, &garbage), Err(InterfaceError::NoData));
+        assert_eq!(receive(&mut uart, encoded), Err(InterfaceError::NoData));
+        assert_eq!(receive(&mut uart, encoded), Ok(PAYLOAD.len()));
+
+        assert_eq!(
+            uart.stats,
+            UartStats {
+                received: 4,
+                parity_errors: 1,
+                framing_errors: 1,
+                oversized: 1,
+                invalid: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn uart_discards_frame_after_overrun() {
+        let mut buffers = Buffers::new();
+        let mut uart = TestUart::new(0, &mut buffers);
+        let mut buf = [0u8; UartFrame::<MTU>::MAX_ENCODED_LEN];
+        let encoded = encoded(&mut buf);
+        let mut rx = [0u8; MTU];
+
+        // Bytes are lost if the RX FIFO is full and the receiver skips the
+        // next frame, because the end of the lost frame is not known
+        uart.uart.feed(encoded);
+        assert_eq!(uart.uart.rx_overruns, encoded.len() - config::FIFO_DEPTH);
+        assert_eq!(uart.receive::<MTU>(&mut rx), Err(InterfaceError::NoData));
+        assert_eq!(receive(&mut uart, encoded), Err(InterfaceError::NoData));
+        assert_eq!(receive(&mut uart, encoded), Ok(PAYLOAD.len()));
+
+        assert_eq!(
+            uart.stats,
+            UartStats {
+                received: 1,
+                overrun_errors: 1,
+                ..Default::default()
+            }
+        );
+    }
 }
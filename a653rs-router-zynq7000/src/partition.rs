@@ -2,10 +2,16 @@
 
 use a653rs::bindings::{ApexPartitionP4, OperatingMode};
 use a653rs::prelude::{Name, Partition, PartitionExt, StartContext};
-use a653rs_router::prelude::{RouterConfig, RouterState, VirtualLinksConfig};
+use a653rs_router::prelude::{
+    PortName, RouterConfig, RouterState, RoutingModesConfig, SchedulerConfig, VirtualLinksConfig,
+};
 use a653rs_router_zynq7000::UartNetworkInterface;
 use a653rs_xng::apex::XngHypervisor;
-use core::str::FromStr;
+use core::{
+    ptr::{addr_of, addr_of_mut},
+    str::FromStr,
+    time::Duration,
+};
 use log::*;
 
 #[cfg(feature = "log")]
@@ -27,6 +33,10 @@ static LOGGER: XalLogger = XalLogger;
 
 static mut ROUTER: Option<RouterState<XngHypervisor, NetIntf, INTERFACES, PORTS>> = None;
 static mut VL_CFG: Option<VirtualLinksConfig<INPUTS, OUTPUTS>> = None;
+static mut SCHEDULER_CFG: Option<SchedulerConfig> = None;
+static mut MODES_CFG: Option<RoutingModesConfig<INPUTS, OUTPUTS>> = None;
+static mut MODE_PORT: Option<PortName> = None;
+static mut PERIOD: Option<Duration> = None;
 
 #[derive(Debug)]
 struct RouterPartition;
@@ -40,7 +50,11 @@ impl Partition<XngHypervisor> for RouterPartition {
         let cfg: RouterConfig<INPUTS, OUTPUTS, INTERFACES, PORTS> =
             postcard::from_bytes(&cfg).expect("Failed to read configuration");
         info!("Have router configuration {:?}", cfg);
-        _ = unsafe { VL_CFG.insert(cfg.virtual_links) };
+        _ = unsafe { (&mut *addr_of_mut!(VL_CFG)).insert(cfg.virtual_links) };
+        _ = unsafe { (&mut *addr_of_mut!(SCHEDULER_CFG)).insert(cfg.scheduler) };
+        _ = unsafe { (&mut *addr_of_mut!(MODES_CFG)).insert(cfg.modes) };
+        unsafe { *addr_of_mut!(MODE_PORT) = cfg.mode_port };
+        _ = unsafe { (&mut *addr_of_mut!(PERIOD)).insert(cfg.period) };
         let router = RouterState::create::<NetIntf>(
             ctx,
             Name::from_str(NAME).unwrap(),
@@ -52,8 +66,8 @@ impl Partition<XngHypervisor> for RouterPartition {
             entry_point,
         )
         .expect("Failed to init router state");
-        _ = unsafe { ROUTER.insert(router) };
-        let router = unsafe { ROUTER.as_ref() }.unwrap();
+        _ = unsafe { (&mut *addr_of_mut!(ROUTER)).insert(router) };
+        let router = unsafe { (&*addr_of!(ROUTER)).as_ref() }.unwrap();
         info!("Starting router process");
         router.start().expect("Failed to start process");
         info!("Started router process");
@@ -69,19 +83,48 @@ extern "C" fn entry_point() {
     use a653rs::prelude::ApexTimeP4Ext;
 
     info!("Running router entry_point");
-    let router = unsafe { ROUTER.as_ref() }.unwrap();
-    let cfg = unsafe { VL_CFG.as_ref() }.unwrap().clone();
+    let router = unsafe { (&*addr_of!(ROUTER)).as_ref() }.unwrap();
+    let cfg = unsafe { (&*addr_of!(VL_CFG)).as_ref() }.unwrap().clone();
+    let scheduler_cfg = unsafe { (&*addr_of!(SCHEDULER_CFG)).as_ref() }.unwrap();
+    let modes_cfg = unsafe { (&*addr_of!(MODES_CFG)).as_ref() }.unwrap();
+    let mode_port = unsafe { (&*addr_of!(MODE_PORT)).as_ref() };
+    let period = unsafe { PERIOD }.unwrap();
+    let mut period_start = XngHypervisor::get_time().unwrap_duration();
     let mut router = router
-        .router::<INPUTS, OUTPUTS, MTU>(cfg, &XngHypervisor::get_time().unwrap_duration())
+        .modal_router::<INPUTS, OUTPUTS, MTU>(
+            cfg,
+            scheduler_cfg,
+            modes_cfg,
+            mode_port,
+            &period_start,
+        )
         .unwrap();
     loop {
+        // The process never waits for its next period, so the start of a
+        // period is detected from the time.
+        let now = XngHypervisor::get_time().unwrap_duration();
+        if now >= period_start + period {
+            period_start = now;
+            if let Err(e) = router.read_mode_request() {
+                debug!("Failed to read mode request: {}", e);
+            }
+            if router.apply_mode_request(&period_start) {
+                info!("Switched to routing mode {}", router.active_mode());
+            }
+        }
         let res = router.forward::<MTU, _>(&XngHypervisor);
+        NetIntf::poll();
         #[cfg(feature = "log")]
         {
             use a653rs_router::prelude::Error;
             use log::*;
             match res {
-                Ok(Some(v)) => debug!("Forwarded VL {}", v),
+                Ok(Some(r)) if r.is_ok() => debug!("Forwarded VL {}", r.vl),
+                Ok(Some(r)) => {
+                    for (dst, e) in r.failures() {
+                        debug!("Failed to forward VL {} to {:?}: {}", r.vl, dst, e)
+                    }
+                }
                 Ok(None) => trace!("Scheduled no VL"),
                 Err(Error::Port(e)) => trace!("Port send/receive failed temporarily: {}", e),
                 Err(e) => debug!("Failed to forward message on VL: {}", e),
@@ -0,0 +1,107 @@
+//! Register access of 16550 compatible UARTs.
+
+use uart_xilinx::MmioUartAxi16550;
+
+/// Bits of the line status register.
+pub mod lsr {
+    /// The RX FIFO contains data.
+    pub const DATA_READY: u8 = 1 << 0;
+    /// A received byte was lost, because the RX FIFO was full.
+    pub const OVERRUN_ERROR: u8 = 1 << 1;
+    /// The byte at the head of the RX FIFO has a wrong parity bit.
+    pub const PARITY_ERROR: u8 = 1 << 2;
+    /// The byte at the head of the RX FIFO has no valid stop bit.
+    pub const FRAMING_ERROR: u8 = 1 << 3;
+    /// The line was held low for longer than a byte.
+    pub const BREAK_INTERRUPT: u8 = 1 << 4;
+}
+
+/// Register operations of a 16550 compatible UART that are used by the
+/// network interface.
+pub trait Uart16550 {
+    /// Creates the UART with registers at `base_address`.
+    fn new(base_address: usize) -> Self;
+
+    /// Sets the clock divisor for the baud rate.
+    fn init(&mut self, clock_rate: usize, baud_rate: usize);
+
+    /// Reads the interrupt enable register.
+    fn read_ier(&mut self) -> u8;
+
+    /// Writes the interrupt enable register.
+    fn write_ier(&mut self, value: u8);
+
+    /// Writes the FIFO control register.
+    fn write_fcr(&mut self, value: u8);
+
+    /// Writes the line control register.
+    fn write_lcr(&mut self, value: u8);
+
+    /// Writes the modem control register.
+    fn write_mcr(&mut self, value: u8);
+
+    /// Reads the line status register.
+    fn read_lsr(&mut self) -> u8;
+
+    /// Reads the modem status register.
+    fn read_msr(&mut self) -> u8;
+
+    /// Reads a byte from the RX FIFO, if there is one.
+    fn read_byte(&mut self) -> Option<u8>;
+
+    /// Writes a byte to the TX FIFO.
+    fn write_byte(&mut self, value: u8);
+
+    /// Whether the TX FIFO is empty.
+    fn is_transmitter_holding_register_empty(&mut self) -> bool;
+}
+
+impl Uart16550 for MmioUartAxi16550<'static> {
+    fn new(base_address: usize) -> Self {
+        MmioUartAxi16550::new(base_address)
+    }
+
+    fn init(&mut self, clock_rate: usize, baud_rate: usize) {
+        MmioUartAxi16550::init(self, clock_rate, baud_rate)
+    }
+
+    fn read_ier(&mut self) -> u8 {
+        MmioUartAxi16550::read_ier(self)
+    }
+
+    fn write_ier(&mut self, value: u8) {
+        MmioUartAxi16550::write_ier(self, value)
+    }
+
+    fn write_fcr(&mut self, value: u8) {
+        MmioUartAxi16550::write_fcr(self, value)
+    }
+
+    fn write_lcr(&mut self, value: u8) {
+        MmioUartAxi16550::write_lcr(self, value)
+    }
+
+    fn write_mcr(&mut self, value: u8) {
+        MmioUartAxi16550::write_mcr(self, value)
+    }
+
+    fn read_lsr(&mut self) -> u8 {
+        MmioUartAxi16550::read_lsr(self)
+    }
+
+    fn read_msr(&mut self) -> u8 {
+        MmioUartAxi16550::read_msr(self)
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        MmioUartAxi16550::read_byte(self)
+    }
+
+    fn write_byte(&mut self, value: u8) {
+        MmioUartAxi16550::write_byte(self, value)
+    }
+
+    fn is_transmitter_holding_register_empty(&mut self) -> bool {
+        MmioUartAxi16550::is_transmitter_holding_register_empty(self)
+    }
+}
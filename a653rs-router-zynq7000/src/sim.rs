@@ -0,0 +1,120 @@
+//! Simulated 16550 UART for host tests.
+
+use crate::registers::{lsr, Uart16550};
+use heapless::{Deque, Vec};
+
+/// Depth of the RX and TX FIFO.
+const FIFO_DEPTH: usize = 16;
+
+/// Maximum number of transmitted bytes that are kept.
+const LINE_LEN: usize = 4096;
+
+/// A UART whose FIFOs are filled and drained by the test.
+///
+/// Each check whether the TX FIFO is empty transmits one byte from the FIFO to
+/// the line, so the network interface makes progress while polling.
+#[derive(Debug, Default)]
+pub struct SimulatedUart {
+    pub base_address: usize,
+    pub baud_rate: usize,
+    /// Received bytes and their line status errors.
+    rx_fifo: Deque<(u8, u8), FIFO_DEPTH>,
+    /// Whether a byte was lost since the line status was read.
+    overrun: bool,
+    tx_fifo: Deque<u8, FIFO_DEPTH>,
+    /// Bytes that were transmitted.
+    pub line: Vec<u8, LINE_LEN>,
+    /// Received bytes that were lost, because the RX FIFO was full.
+    pub rx_overruns: usize,
+    /// Written bytes that were lost, because the TX FIFO was full.
+    pub tx_overruns: usize,
+}
+
+impl SimulatedUart {
+    /// Receives bytes from the line. Bytes that do not fit into the RX FIFO
+    /// are lost.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.feed_with_errors(bytes, 0)
+    }
+
+    /// Receives bytes from the line, that are reported with the line status
+    /// `errors`, e.g. [`lsr::PARITY_ERROR`].
+    pub fn feed_with_errors(&mut self, bytes: &[u8], errors: u8) {
+        for b in bytes {
+            if self.rx_fifo.push_back((*b, errors)).is_err() {
+                self.rx_overruns += 1;
+                self.overrun = true;
+            }
+        }
+    }
+
+    /// Transmits all bytes in the TX FIFO.
+    pub fn flush(&mut self) {
+        while self.transmit() {}
+    }
+
+    fn transmit(&mut self) -> bool {
+        match self.tx_fifo.pop_front() {
+            Some(b) => {
+                _ = self.line.push(b);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Uart16550 for SimulatedUart {
+    fn new(base_address: usize) -> Self {
+        Self {
+            base_address,
+            ..Default::default()
+        }
+    }
+
+    fn init(&mut self, _clock_rate: usize, baud_rate: usize) {
+        self.baud_rate = baud_rate;
+    }
+
+    fn read_ier(&mut self) -> u8 {
+        0
+    }
+
+    fn write_ier(&mut self, _value: u8) {}
+
+    fn write_fcr(&mut self, _value: u8) {}
+
+    fn write_lcr(&mut self, _value: u8) {}
+
+    fn write_mcr(&mut self, _value: u8) {}
+
+    fn read_lsr(&mut self) -> u8 {
+        let mut status = match self.rx_fifo.front() {
+            Some((_, errors)) => lsr::DATA_READY | errors,
+            None => 0,
+        };
+        if core::mem::take(&mut self.overrun) {
+            status |= lsr::OVERRUN_ERROR;
+        }
+        status
+    }
+
+    fn read_msr(&mut self) -> u8 {
+        0
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        self.rx_fifo.pop_front().map(|(b, _)| b)
+    }
+
+    fn write_byte(&mut self, value: u8) {
+        if self.tx_fifo.push_back(value).is_err() {
+            self.tx_overruns += 1;
+        }
+    }
+
+    fn is_transmitter_holding_register_empty(&mut self) -> bool {
+        _ = self.transmit();
+        self.tx_fifo.is_empty()
+    }
+}
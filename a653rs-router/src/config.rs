@@ -1,10 +1,15 @@
-use crate::{ports::PortError, prelude::InterfaceConfig, types::VirtualLinkId};
+use crate::{
+    ports::PortError,
+    prelude::InterfaceConfig,
+    scheduler::{is_aligned, TableSlot, MAX_TABLE_SLOTS},
+    types::VirtualLinkId,
+};
 use a653rs::{
     bindings::{MessageRange, MessageSize, QueuingDiscipline as ApexQueuingDiscipline, StackSize},
     prelude::Name,
 };
 use core::{ops::Deref, str::FromStr, time::Duration};
-use heapless::{FnvIndexSet, LinearMap, String};
+use heapless::{FnvIndexSet, LinearMap, String, Vec};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -92,6 +97,87 @@ pub struct RouterConfig<const IN: usize, const OUT: usize, const IFS: usize, con
     /// Port configuration
     #[cfg_attr(feature = "serde", serde(default))]
     pub ports: PortsConfig<PORTS>,
+
+    /// Scheduler that selects the next virtual link to forward.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub scheduler: SchedulerConfig,
+
+    /// Additional routing modes that the router can switch to at runtime.
+    /// The virtual links and scheduler above form the [`DEFAULT_MODE`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub modes: RoutingModesConfig<IN, OUT>,
+
+    /// Sampling port receiver from which requests to switch the routing mode
+    /// are read.
+    /// A message holds the name of the requested mode and may be padded with
+    /// zero bytes. The port must not be the source of a virtual link.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mode_port: Option<PortName>,
+}
+
+/// Name of the routing mode formed by the virtual links and scheduler at the
+/// top level of a [`RouterConfig`].
+pub const DEFAULT_MODE: &str = "default";
+
+/// Maximum number of additional routing modes.
+pub const MAX_MODES: usize = 4;
+
+/// Maximum length of the name of a routing mode.
+pub const MAX_MODE_NAME: usize = 20;
+
+/// The name of a routing mode.
+pub type ModeName = String<MAX_MODE_NAME>;
+
+/// Routing modes indexed by their name.
+pub type RoutingModesConfig<const IN: usize, const OUT: usize> =
+    LinearMap<ModeName, RoutingModeConfig<IN, OUT>, MAX_MODES>;
+
+/// A set of virtual links that are forwarded using the same ports and
+/// interfaces as the other routing modes.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RoutingModeConfig<const IN: usize, const OUT: usize> {
+    /// Forwarding table
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub virtual_links: VirtualLinksConfig<IN, OUT>,
+
+    /// Scheduler that selects the next virtual link to forward.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub scheduler: SchedulerConfig,
+}
+
+/// Scheduler configuration.
+///
+/// Variants are externally tagged, so configurations can also be read by
+/// non-self-describing formats like postcard. In YAML the variant is written
+/// as a tag, e.g. `!static_table`.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[allow(clippy::large_enum_variant)]
+pub enum SchedulerConfig {
+    /// Round-robin scheduling of virtual links whose period has passed.
+    #[default]
+    DeadlineRr,
+    /// Time-triggered scheduling of virtual links using a cyclic table.
+    ///
+    /// The major frame must be a multiple of the period of the router
+    /// partition.
+    StaticTable {
+        /// Duration of one cycle of the table.
+        major_frame: Duration,
+        /// Slots ordered by their offset into the major frame.
+        slots: Vec<TableSlot, MAX_TABLE_SLOTS>,
+    },
+    /// Scheduling of the virtual link with the highest priority whose period
+    /// has passed, with the earliest deadline as a tiebreaker.
+    Priority {
+        /// Raises the priority of a virtual link by one each time this
+        /// duration passes after its deadline, so virtual links of low priority
+        /// are eventually forwarded.
+        #[cfg_attr(feature = "serde", serde(default))]
+        aging: Option<Duration>,
+    },
 }
 
 /// Sampling port destination configuration
@@ -223,6 +309,9 @@ impl<const IN: usize, const OUT: usize, const IFS: usize, const PORTS: usize>
             virtual_links: Default::default(),
             interfaces: Default::default(),
             ports: Default::default(),
+            scheduler: Default::default(),
+            modes: Default::default(),
+            mode_port: Default::default(),
         }
     }
 
@@ -260,6 +349,8 @@ pub enum RouterConfigError {
     Storage,
     /// Invalid configuration format
     Format,
+    /// The specified routing mode is invalid.
+    Mode,
 }
 
 /// Virtual link between one source and multiple destinations.
@@ -275,6 +366,102 @@ pub struct VirtualLinkConfig<const D: usize> {
     pub dsts: FnvIndexSet<PortName, D>,
     /// Minimum transmission interval
     pub period: Duration,
+    /// Maximum number of messages to forward each time the virtual link is
+    /// scheduled.
+    /// Only queuing port receivers and network interfaces may be drained of
+    /// more than one message.
+    #[cfg_attr(feature = "serde", serde(default = "default_max_messages"))]
+    pub max_messages: usize,
+    /// Maximum number of bytes to forward each time the virtual link is
+    /// scheduled.
+    /// A further message is only forwarded if a message of the MTU of the
+    /// source would not exceed this limit. The first message is always
+    /// forwarded.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_bytes: Option<usize>,
+    /// Handling of invalid messages.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub validity: ValidityPolicy,
+    /// When messages from a sampling port receiver are forwarded.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub forwarding: ForwardingMode,
+    /// Priority of the virtual link if multiple virtual links are due.
+    /// Higher values are forwarded first.
+    /// Used by the priority scheduler and by network interfaces that
+    /// prioritize frames on the network.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priority: u8,
+    /// Whether messages on the network carry sequence numbers.
+    /// Messages from the network that repeat or precede the sequence number of
+    /// the last message are dropped.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sequence_numbers: bool,
+    /// Redundant transmission of the virtual link over two networks.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub redundancy: Option<RedundancyConfig>,
+}
+
+/// A pair of network interfaces that transmit a virtual link redundantly, like
+/// the two networks of ARINC 664 P7.
+///
+/// Interface `a` is used as the source or a destination of the virtual link
+/// and interface `b` is used alongside it. Messages are sent on both
+/// interfaces and received from both of them. The first valid copy of a
+/// message is forwarded and the other copy is dropped based on its sequence
+/// number. Redundant virtual links therefore always use sequence numbers.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedundancyConfig {
+    /// Interface of the first network.
+    pub a: InterfaceName,
+    /// Interface of the second network.
+    pub b: InterfaceName,
+    /// Maximum time between the arrival of the two copies of a message.
+    /// Once it has passed since the last accepted message, the next message is
+    /// accepted regardless of its sequence number.
+    pub skew_max: Duration,
+}
+
+/// Handling of invalid messages of a virtual link.
+///
+/// Messages from sampling ports are invalid if they are older than the refresh
+/// period of the port. Messages from the network are invalid if the sending
+/// router marked them as invalid.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ValidityPolicy {
+    /// Forward invalid messages as if they were valid.
+    #[default]
+    Forward,
+    /// Drop invalid messages.
+    ///
+    /// Sampling ports that are destinations of the virtual link will become
+    /// invalid once their refresh period has passed.
+    Drop,
+    /// Forward invalid messages and mark them as invalid on the network.
+    Mark,
+}
+
+/// When the message of a sampling port receiver is forwarded.
+///
+/// A sampling port always returns the last message that was written to it, so
+/// the same message is read each time the virtual link is scheduled until the
+/// producer writes a new one.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardingMode {
+    /// Forward the message each time the virtual link is scheduled.
+    #[default]
+    Periodic,
+    /// Forward the message only if its content or validity changed since it
+    /// was last forwarded to all destinations.
+    OnUpdate,
+}
+
+const fn default_max_messages() -> usize {
+    1
 }
 
 mod sealed {
@@ -351,17 +538,109 @@ impl<const IN: usize, const OUT: usize, const IFS: usize, const PORTS: usize>
 
     /// Build the configuration.
     pub fn build(&self) -> CfgResult<IN, OUT, IFS, PORTS> {
-        if self
-            .cfg
-            .virtual_links
-            .iter()
-            .any(|vl| vl.1.period.is_zero())
-        {
-            return Err(RouterConfigError::Schedule);
+        self.check_mode(&self.cfg.virtual_links, &self.cfg.scheduler)?;
+        for (_, mode) in self.cfg.modes.iter() {
+            self.check_mode(&mode.virtual_links, &mode.scheduler)?;
+        }
+        if let Some(port) = &self.cfg.mode_port {
+            self.check_mode_port(port)?;
         }
         Ok(self.cfg.clone())
     }
 
+    /// Checks the schedule of the virtual links of a routing mode.
+    fn check_mode(
+        &self,
+        virtual_links: &VirtualLinksConfig<IN, OUT>,
+        scheduler: &SchedulerConfig,
+    ) -> Result<(), RouterConfigError> {
+        for (_, vl) in virtual_links.iter() {
+            // Only queuing port receivers and network interfaces can be
+            // drained of multiple messages and only sampling port receivers
+            // return the same message repeatedly
+            let is_sampling =
+                matches!(self.cfg.ports.get(&vl.src), Some(PortConfig::SamplingIn(_)));
+            if vl.period.is_zero()
+                || vl.max_messages == 0
+                || (vl.max_messages > 1 && !self.is_queue(&vl.src))
+                || (vl.forwarding == ForwardingMode::OnUpdate && !is_sampling)
+            {
+                return Err(RouterConfigError::Schedule);
+            }
+        }
+        if let SchedulerConfig::StaticTable { major_frame, .. } = scheduler {
+            if !is_aligned(major_frame, &self.cfg.period) {
+                return Err(RouterConfigError::Schedule);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the mode port is a sampling port receiver that fits the
+    /// name of a mode and is not used by any virtual link.
+    fn check_mode_port(&self, port: &PortName) -> Result<(), RouterConfigError> {
+        let Some(PortConfig::SamplingIn(cfg)) = self.cfg.ports.get(port) else {
+            return Err(RouterConfigError::Port);
+        };
+        let is_source = self
+            .cfg
+            .modes
+            .values()
+            .map(|m| &m.virtual_links)
+            .chain([&self.cfg.virtual_links])
+            .any(|vls| vls.values().any(|vl| &vl.src == port));
+        if cfg.msg_size as usize > MAX_MODE_NAME || is_source {
+            return Err(RouterConfigError::Port);
+        }
+        Ok(())
+    }
+
+    /// Reads requests to switch the routing mode from the sampling port
+    /// receiver `port`.
+    ///
+    /// # Errors
+    /// Returns an error if the port name was invalid.
+    pub fn mode_port(&mut self, port: &str) -> BuilderResult<'_, IN, OUT, IFS, PORTS> {
+        self.cfg.mode_port = Some(PortName::from_str(port)?);
+        Ok(self)
+    }
+
+    /// Adds a routing mode that the router can switch to at runtime.
+    ///
+    /// # Errors
+    /// Returns an error if a mode with this name already exists, there is
+    /// insufficient storage or the mode uses unknown ports or interfaces.
+    pub fn mode(
+        &mut self,
+        name: &str,
+        mode: RoutingModeConfig<IN, OUT>,
+    ) -> BuilderResult<'_, IN, OUT, IFS, PORTS> {
+        let name = ModeName::from_str(name).or(Err(RouterConfigError::Mode))?;
+        if name == DEFAULT_MODE || self.cfg.modes.contains_key(&name) {
+            return Err(RouterConfigError::Mode);
+        }
+        for (_, vl) in mode.virtual_links.iter() {
+            if !self.contains_resource(&vl.src) {
+                return Err(RouterConfigError::Source);
+            }
+            if !vl.dsts.iter().all(|d| self.contains_resource(d)) {
+                return Err(RouterConfigError::Destination);
+            }
+        }
+        _ = self
+            .cfg
+            .modes
+            .insert(name, mode)
+            .or(Err(RouterConfigError::Storage))?;
+        Ok(self)
+    }
+
+    /// Selects the scheduler of the router.
+    pub fn scheduler(&mut self, scheduler: SchedulerConfig) -> &mut Self {
+        self.cfg.scheduler = scheduler;
+        self
+    }
+
     /// Adds a port to the configuration.
     ///
     /// # Errors
@@ -427,6 +706,13 @@ impl<const IN: usize, const OUT: usize, const IFS: usize, const PORTS: usize>
         }
     }
 
+    /// Whether `src` is a queuing port receiver or a network interface, which
+    /// hold messages that were not received yet.
+    fn is_queue(&self, src: &PortName) -> bool {
+        self.cfg.interfaces.contains_key(src)
+            || matches!(self.cfg.ports.get(src), Some(PortConfig::QueuingIn(_)))
+    }
+
     fn contains_resource(&mut self, dst: &PortName) -> bool {
         self.cfg.interfaces.contains_key(dst) || self.cfg.ports.contains_key(dst)
     }
@@ -449,6 +735,127 @@ impl<const IN: usize, const OUT: usize, const IFS: usize, const PORTS: usize>
         Ok(self)
     }
 
+    /// Limits how much data is forwarded each time a virtual link is
+    /// scheduled.
+    ///
+    /// Queuing port receivers and network interfaces are drained of up to
+    /// `max_messages` messages or until another message could exceed
+    /// `max_bytes`.
+    ///
+    /// # Errors
+    /// Returns an error if the virtual link (VL) does not exist, `max_messages`
+    /// is zero or the source of the VL is neither a queuing port receiver nor a
+    /// network interface but `max_messages` is larger than one.
+    pub fn budget(
+        &mut self,
+        vl_id: u16,
+        max_messages: usize,
+        max_bytes: Option<usize>,
+    ) -> BuilderResult<'_, IN, OUT, IFS, PORTS> {
+        let vl = VirtualLinkId::from(vl_id);
+        let src = self.find_vl(&vl)?.src.clone();
+        if max_messages == 0 || (max_messages > 1 && !self.is_queue(&src)) {
+            return Err(RouterConfigError::Schedule);
+        }
+        let vl = self.find_vl(&vl)?;
+        vl.max_messages = max_messages;
+        vl.max_bytes = max_bytes;
+        Ok(self)
+    }
+
+    /// Sets how invalid messages of a virtual link are handled.
+    ///
+    /// # Errors
+    /// Returns an error if the virtual link (VL) does not exist.
+    pub fn validity(
+        &mut self,
+        vl_id: u16,
+        policy: ValidityPolicy,
+    ) -> BuilderResult<'_, IN, OUT, IFS, PORTS> {
+        let vl = VirtualLinkId::from(vl_id);
+        let vl = self.find_vl(&vl)?;
+        vl.validity = policy;
+        Ok(self)
+    }
+
+    /// Sets the priority of a virtual link.
+    ///
+    /// # Errors
+    /// Returns an error if the virtual link (VL) does not exist.
+    pub fn priority(&mut self, vl_id: u16, priority: u8) -> BuilderResult<'_, IN, OUT, IFS, PORTS> {
+        let vl = VirtualLinkId::from(vl_id);
+        let vl = self.find_vl(&vl)?;
+        vl.priority = priority;
+        Ok(self)
+    }
+
+    /// Sets whether the messages of a virtual link carry sequence numbers on
+    /// the network.
+    ///
+    /// # Errors
+    /// Returns an error if the virtual link (VL) does not exist.
+    pub fn sequence_numbers(
+        &mut self,
+        vl_id: u16,
+        enabled: bool,
+    ) -> BuilderResult<'_, IN, OUT, IFS, PORTS> {
+        let vl = VirtualLinkId::from(vl_id);
+        let vl = self.find_vl(&vl)?;
+        vl.sequence_numbers = enabled;
+        Ok(self)
+    }
+
+    /// Transmits a virtual link redundantly on the interfaces `a` and `b`.
+    ///
+    /// # Errors
+    /// Returns an error if the virtual link (VL) does not exist, `a` and `b`
+    /// are not two distinct interfaces, `a` is neither the source nor a
+    /// destination of the VL or `b` is already one of them.
+    pub fn redundancy(
+        &mut self,
+        vl_id: u16,
+        a: &str,
+        b: &str,
+        skew_max: Duration,
+    ) -> BuilderResult<'_, IN, OUT, IFS, PORTS> {
+        let a = InterfaceName::from_str(a).or(Err(RouterConfigError::Interface))?;
+        let b = InterfaceName::from_str(b).or(Err(RouterConfigError::Interface))?;
+        if a == b || !self.cfg.interfaces.contains_key(&a) || !self.cfg.interfaces.contains_key(&b)
+        {
+            return Err(RouterConfigError::Interface);
+        }
+        let vl = VirtualLinkId::from(vl_id);
+        let vl = self.find_vl(&vl)?;
+        let uses = |name: &InterfaceName| vl.src == *name || vl.dsts.contains(name);
+        if !uses(&a) || uses(&b) {
+            return Err(RouterConfigError::Interface);
+        }
+        vl.redundancy = Some(RedundancyConfig { a, b, skew_max });
+        Ok(self)
+    }
+
+    /// Sets when the message of a sampling port receiver is forwarded.
+    ///
+    /// # Errors
+    /// Returns an error if the virtual link (VL) does not exist or the mode is
+    /// [`ForwardingMode::OnUpdate`] but the source of the VL is not a sampling
+    /// port receiver.
+    pub fn forwarding(
+        &mut self,
+        vl_id: u16,
+        mode: ForwardingMode,
+    ) -> BuilderResult<'_, IN, OUT, IFS, PORTS> {
+        let vl = VirtualLinkId::from(vl_id);
+        let src = self.find_vl(&vl)?.src.clone();
+        let is_sampling = matches!(self.cfg.ports.get(&src), Some(PortConfig::SamplingIn(_)));
+        if mode == ForwardingMode::OnUpdate && !is_sampling {
+            return Err(RouterConfigError::Schedule);
+        }
+        let vl = self.find_vl(&vl)?;
+        vl.forwarding = mode;
+        Ok(self)
+    }
+
     fn find_vl(
         &mut self,
         id: &VirtualLinkId,
@@ -484,6 +891,13 @@ impl<const IN: usize, const OUT: usize, const IFS: usize, const PORTS: usize>
             src,
             dsts: Default::default(),
             period: Default::default(),
+            max_messages: default_max_messages(),
+            max_bytes: None,
+            validity: Default::default(),
+            forwarding: Default::default(),
+            priority: 0,
+            sequence_numbers: false,
+            redundancy: None,
         };
         let vl_added = self.cfg.virtual_links.insert(vl_id, vl).is_ok();
         if !vl_added {
@@ -571,4 +985,209 @@ mod tests {
         .build()
         .unwrap();
     }
+
+    type Builder = RouterConfigBuilder<8, 8, 8, 8>;
+
+    /// A change of the builder and the result it is expected to have.
+    type Case = (
+        &'static str,
+        fn(&mut Builder) -> Result<(), RouterConfigError>,
+        Result<(), RouterConfigError>,
+    );
+
+    /// A mode that forwards the sensor port, changed by `f`.
+    fn mode(f: impl FnOnce(&mut VirtualLinkConfig<8>)) -> RoutingModeConfig<8, 8> {
+        let mut vl = VirtualLinkConfig {
+            src: PortName::from_str("Sensor").unwrap(),
+            dsts: Default::default(),
+            period: Duration::from_millis(10),
+            max_messages: 1,
+            max_bytes: None,
+            validity: Default::default(),
+            forwarding: Default::default(),
+            priority: 0,
+            sequence_numbers: false,
+            redundancy: None,
+        };
+        f(&mut vl);
+        let mut mode = RoutingModeConfig::default();
+        _ = mode
+            .virtual_links
+            .insert(VirtualLinkId::from(1u16), vl)
+            .unwrap();
+        mode
+    }
+
+    const SKEW_MAX: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn builder_validates_virtual_links() {
+        let cases: &[Case] = &[
+            (
+                "budget of sampling source",
+                |b| b.budget(1, 1, Some(1_000)).map(drop),
+                Ok(()),
+            ),
+            (
+                "budget of queuing source",
+                |b| b.budget(2, 4, None).map(drop),
+                Ok(()),
+            ),
+            (
+                "budget of network source",
+                |b| b.budget(3, 4, None)?.build().map(drop),
+                Ok(()),
+            ),
+            (
+                "several messages of sampling source",
+                |b| b.budget(1, 2, None).map(drop),
+                Err(RouterConfigError::Schedule),
+            ),
+            (
+                "no messages",
+                |b| b.budget(2, 0, None).map(drop),
+                Err(RouterConfigError::Schedule),
+            ),
+            (
+                "on update of sampling source",
+                |b| b.forwarding(1, ForwardingMode::OnUpdate).map(drop),
+                Ok(()),
+            ),
+            (
+                "on update of queuing source",
+                |b| b.forwarding(2, ForwardingMode::OnUpdate).map(drop),
+                Err(RouterConfigError::Schedule),
+            ),
+            (
+                "redundant interfaces",
+                |b| b.redundancy(1, "NetA", "NetB", SKEW_MAX).map(drop),
+                Ok(()),
+            ),
+            (
+                "same interface twice",
+                |b| b.redundancy(1, "NetA", "NetA", SKEW_MAX).map(drop),
+                Err(RouterConfigError::Interface),
+            ),
+            (
+                "interface a is not a destination",
+                |b| b.redundancy(1, "NetB", "NetA", SKEW_MAX).map(drop),
+                Err(RouterConfigError::Interface),
+            ),
+            (
+                "redundant port",
+                |b| b.redundancy(1, "NetA", "Sensor", SKEW_MAX).map(drop),
+                Err(RouterConfigError::Interface),
+            ),
+            (
+                "mode",
+                |b| b.mode("degraded", mode(|_| {}))?.build().map(drop),
+                Ok(()),
+            ),
+            (
+                "mode defined twice",
+                |b| {
+                    b.mode("degraded", mode(|_| {}))?
+                        .mode("degraded", mode(|_| {}))
+                        .map(drop)
+                },
+                Err(RouterConfigError::Mode),
+            ),
+            (
+                "default mode",
+                |b| b.mode(DEFAULT_MODE, mode(|_| {})).map(drop),
+                Err(RouterConfigError::Mode),
+            ),
+            (
+                "mode with unknown port",
+                |b| {
+                    let unknown = mode(|vl| vl.src = PortName::from_str("Actuator").unwrap());
+                    b.mode("unknown", unknown).map(drop)
+                },
+                Err(RouterConfigError::Source),
+            ),
+            (
+                "mode with unscheduled virtual link",
+                |b| {
+                    b.mode("unscheduled", mode(|vl| vl.period = Duration::ZERO))?
+                        .build()
+                        .map(drop)
+                },
+                Err(RouterConfigError::Schedule),
+            ),
+            (
+                "mode port",
+                |b| b.mode_port("ModeRequest")?.build().map(drop),
+                Ok(()),
+            ),
+            (
+                "queuing mode port",
+                |b| b.mode_port("Commands")?.build().map(drop),
+                Err(RouterConfigError::Port),
+            ),
+            (
+                "mode port longer than mode names",
+                |b| b.mode_port("Sensor")?.build().map(drop),
+                Err(RouterConfigError::Port),
+            ),
+            (
+                "mode port forwarded by a mode",
+                |b| {
+                    let forwarded = mode(|vl| vl.src = PortName::from_str("ModeRequest").unwrap());
+                    b.mode("forwarded", forwarded)?
+                        .mode_port("ModeRequest")?
+                        .build()
+                        .map(drop)
+                },
+                Err(RouterConfigError::Port),
+            ),
+        ];
+        for (case, change, expected) in cases {
+            let mut builder = RouterConfig::builder(
+                10_000,
+                Duration::from_millis(500),
+                Duration::from_millis(10),
+            );
+            _ = builder
+                .port(
+                    "Sensor",
+                    PortConfig::sampling_in(1_000, Duration::from_millis(10)),
+                )
+                .unwrap()
+                .port(
+                    "Commands",
+                    PortConfig::queuing_in(QueuingDiscipline::Fifo, 10, 1_000),
+                )
+                .unwrap()
+                .port(
+                    "ModeRequest",
+                    PortConfig::sampling_in(MAX_MODE_NAME as u32, Duration::from_millis(10)),
+                )
+                .unwrap()
+                .interface(
+                    "NetA",
+                    InterfaceConfig::new("a", "a", DataRate::b(10_000_000), 1_000),
+                )
+                .unwrap()
+                .interface(
+                    "NetB",
+                    InterfaceConfig::new("b", "b", DataRate::b(10_000_000), 1_000),
+                )
+                .unwrap()
+                .virtual_link(1, "Sensor")
+                .unwrap()
+                .destination(1, "NetA")
+                .unwrap()
+                .schedule(1, Duration::from_millis(10))
+                .unwrap()
+                .virtual_link(2, "Commands")
+                .unwrap()
+                .schedule(2, Duration::from_millis(10))
+                .unwrap()
+                .virtual_link(3, "NetB")
+                .unwrap()
+                .schedule(3, Duration::from_millis(10))
+                .unwrap();
+            assert_eq!(&change(&mut builder), expected, "{case}");
+        }
+    }
 }
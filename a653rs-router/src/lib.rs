@@ -68,25 +68,38 @@ mod error;
 #[macro_use]
 mod macros;
 
+mod modes;
 mod network;
 mod partition;
 mod ports;
 mod process;
 mod router;
 mod scheduler;
+mod sequence;
+mod stats;
 mod types;
+#[cfg(feature = "uart")]
+mod uart;
 
 /// Standard Prelude to be used by router partitions and network interface
 /// implementations.
 pub mod prelude {
     pub use crate::config::*;
     pub use crate::error::Error;
+    pub use crate::modes::ModalRouter;
     pub use crate::network::{
-        CreateNetworkInterfaceId, InterfaceConfig, InterfaceError, NetworkInterfaceId,
-        PlatformNetworkInterface,
+        CreateNetworkInterfaceId, Frame, InterfaceConfig, InterfaceError, NetworkInterface,
+        NetworkInterfaceId, PlatformNetworkInterface, ShaperStats,
     };
     pub use crate::partition::RouterState;
-    pub use crate::router::Router;
-    pub use crate::scheduler::{InvalidTimeError, TimeSource};
+    pub use crate::ports::PortError;
+    pub use crate::router::{ForwardReport, RouteError, Router};
+    pub use crate::scheduler::{
+        AnyScheduler, DeadlineRrScheduler, InvalidTimeError, PriorityScheduler, Scheduler,
+        StaticTableScheduler, TableSlot, TimeSource, MAX_TABLE_SLOTS,
+    };
+    pub use crate::stats::{DestinationStats, InterfaceStats, RouterStats, VirtualLinkStats};
     pub use crate::types::*;
+    #[cfg(feature = "uart")]
+    pub use crate::uart::UartFrame;
 }
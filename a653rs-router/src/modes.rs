@@ -0,0 +1,217 @@
+//! Routing modes
+
+use crate::{
+    config::{
+        ModeName, PortName, RouterConfigError, RoutingModesConfig, SchedulerConfig,
+        VirtualLinksConfig, DEFAULT_MODE, MAX_MODES, MAX_MODE_NAME,
+    },
+    error::Error,
+    network::PlatformNetworkInterface,
+    ports::PortError,
+    router::{ForwardReport, Router, RouterInput, RouterResources},
+    scheduler::TimeSource,
+    stats::RouterStats,
+    types::VirtualLinkId,
+};
+
+use a653rs::bindings::{ApexQueuingPortP4, ApexSamplingPortP4};
+use core::{
+    fmt::{self, Debug},
+    str::{self, FromStr},
+    time::Duration,
+};
+use heapless::LinearMap;
+
+/// A router that switches between routing modes at runtime.
+///
+/// The routers of all modes are created up-front, so switching the mode can
+/// not fail because of an invalid configuration. A switch is requested using
+/// [`ModalRouter::request_mode`] or by writing the name of the mode to the mode
+/// port of the configuration, which is read by
+/// [`ModalRouter::read_mode_request`]. The switch takes effect once
+/// [`ModalRouter::apply_mode_request`] is called at the start of the next
+/// period of the router process.
+#[derive(Clone)]
+pub struct ModalRouter<'a, const IN: usize, const OUT: usize> {
+    modes: LinearMap<ModeName, Router<'a, IN, OUT>, { MAX_MODES + 1 }>,
+    active: ModeName,
+    requested: Option<ModeName>,
+    mode_port: Option<&'a dyn RouterInput>,
+}
+
+impl<const IN: usize, const OUT: usize> Debug for ModalRouter<'_, IN, OUT> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModalRouter")
+            .field("active", &self.active)
+            .field("requested", &self.requested)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, const IN: usize, const OUT: usize> ModalRouter<'a, IN, OUT> {
+    /// Tries to initialize the routers of the [`DEFAULT_MODE`] and all
+    /// additional `modes`. The default mode is active initially.
+    /// Requests to switch the mode are read from the sampling port receiver
+    /// `mode_port`, if any.
+    ///
+    /// # Errors
+    /// This function will return an error if the configuration of any mode was
+    /// invalid or did not match the provided resources.
+    pub fn try_new<
+        H: ApexQueuingPortP4 + ApexSamplingPortP4,
+        P: PlatformNetworkInterface,
+        const IFS: usize,
+        const PORTS: usize,
+    >(
+        virtual_links_cfg: VirtualLinksConfig<IN, OUT>,
+        scheduler_cfg: &SchedulerConfig,
+        modes_cfg: &RoutingModesConfig<IN, OUT>,
+        mode_port: Option<&PortName>,
+        resources: &'a RouterResources<H, P, IFS, PORTS>,
+        period: &Duration,
+        schedule_start: &Duration,
+    ) -> Result<Self, Error> {
+        let mode_port = mode_port
+            .map(|name| {
+                resources
+                    .sampling_input(name)
+                    .ok_or(RouterConfigError::Port)
+            })
+            .transpose()?;
+        let active = ModeName::from_str(DEFAULT_MODE).or(Err(RouterConfigError::Mode))?;
+        let mut modes = LinearMap::default();
+        let default = Router::try_new(
+            virtual_links_cfg,
+            scheduler_cfg,
+            resources,
+            period,
+            schedule_start,
+        )?;
+        _ = modes
+            .insert(active.clone(), default)
+            .or(Err(RouterConfigError::Storage))?;
+        for (name, cfg) in modes_cfg.iter() {
+            let router = Router::try_new(
+                cfg.virtual_links.clone(),
+                &cfg.scheduler,
+                resources,
+                period,
+                schedule_start,
+            )?;
+            modes
+                .insert(name.clone(), router)
+                .or(Err(RouterConfigError::Storage))?
+                .map(|_| Err(RouterConfigError::Mode))
+                .unwrap_or(Ok(()))?;
+        }
+        Ok(Self {
+            modes,
+            active,
+            requested: None,
+            mode_port,
+        })
+    }
+
+    /// The name of the active mode.
+    pub fn active_mode(&self) -> &str {
+        &self.active
+    }
+
+    /// The router of the active mode.
+    pub fn router(&self) -> &Router<'a, IN, OUT> {
+        // The active mode always exists.
+        &self.modes[&self.active]
+    }
+
+    /// Counters of the mode `name`.
+    ///
+    /// The counters of a mode are reset when it becomes active, so the
+    /// counters of an inactive mode cover its last activation.
+    pub fn mode_stats(&self, name: &str) -> Option<&RouterStats<IN, OUT>> {
+        let name = ModeName::from_str(name).ok()?;
+        self.modes.get(&name).map(Router::stats)
+    }
+
+    /// The router of the active mode.
+    pub fn router_mut(&mut self) -> &mut Router<'a, IN, OUT> {
+        &mut self.modes[&self.active]
+    }
+
+    /// Requests switching to the mode `name`.
+    ///
+    /// A later request replaces an earlier request that has not been applied
+    /// yet. Requesting the active mode cancels an earlier request.
+    ///
+    /// # Errors
+    /// Returns an error if there is no mode with this name.
+    pub fn request_mode(&mut self, name: &str) -> Result<(), Error> {
+        let name = ModeName::from_str(name).or(Err(RouterConfigError::Mode))?;
+        if !self.modes.contains_key(&name) {
+            return Err(RouterConfigError::Mode.into());
+        }
+        self.requested = (name != self.active).then_some(name);
+        Ok(())
+    }
+
+    /// Requests switching to the mode whose name was last written to the mode
+    /// port, if any.
+    ///
+    /// The mode port keeps the last message, so the mode is requested again
+    /// each time this is called. This should be called before
+    /// [`ModalRouter::apply_mode_request`].
+    /// Messages are used regardless of their validity.
+    ///
+    /// # Errors
+    /// Returns an error if the message could not be received or does not
+    /// contain the name of a mode.
+    pub fn read_mode_request(&mut self) -> Result<(), Error> {
+        let Some(port) = self.mode_port else {
+            return Ok(());
+        };
+        let mut buf = [0u8; MAX_MODE_NAME];
+        let frame = port.receive(&VirtualLinkId::default(), &mut buf)?;
+        let name = str::from_utf8(frame.payload)
+            .or(Err(PortError::Receive))?
+            .trim_end_matches('\0');
+        if name.is_empty() {
+            return Ok(());
+        }
+        self.request_mode(name)
+    }
+
+    /// Switches to the requested mode, if any.
+    ///
+    /// This should be called at the start of a period of the router process.
+    /// The schedule of the new mode is restarted at `period_start` and its
+    /// counters are reset. Reports of the previous mode that were not returned
+    /// by [`ModalRouter::forward`] yet are returned first by the new mode.
+    /// Returns whether the mode was switched.
+    pub fn apply_mode_request(&mut self, period_start: &Duration) -> bool {
+        let Some(requested) = self.requested.take() else {
+            return false;
+        };
+        router_debug!(
+            "Switching from routing mode {} to {}",
+            self.active,
+            requested
+        );
+        let pending = self.router_mut().take_pending();
+        self.active = requested;
+        self.router_mut().resume(period_start, pending);
+        true
+    }
+
+    /// Forwards messages of the active mode.
+    ///
+    /// See [`Router::forward`].
+    ///
+    /// # Errors
+    /// Returns an error if no message could be received from the source of the
+    /// scheduled virtual link.
+    pub fn forward<const B: usize, T: TimeSource>(
+        &mut self,
+        time_source: &T,
+    ) -> Result<Option<ForwardReport<OUT>>, Error> {
+        self.router_mut().forward::<B, T>(time_source)
+    }
+}
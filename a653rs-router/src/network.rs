@@ -1,13 +1,16 @@
 use crate::{
     ports::PortError,
     router::{RouterInput, RouterOutput},
-    types::DataRate,
+    types::{DataRate, VirtualLinkId},
 };
 
 use core::{
+    cell::Cell,
     fmt::{Display, Formatter},
     marker::PhantomData,
+    mem::size_of,
     str::FromStr,
+    time::Duration,
 };
 use heapless::String;
 
@@ -41,21 +44,46 @@ pub struct NetworkInterface<P: PlatformNetworkInterface> {
     _p: PhantomData<P>,
     id: NetworkInterfaceId,
     mtu: PayloadSize,
+    shaper: Shaper,
 }
 
 impl<H: PlatformNetworkInterface> NetworkInterface<H> {
-    /// Sends data to the interface.
-    pub fn send(&self, buf: &[u8]) -> Result<usize, InterfaceError> {
-        if buf.len() > self.mtu {
+    /// Sends a frame to the interface.
+    ///
+    /// # Errors
+    /// Returns an error if the frame is too large, sending it would exceed the
+    /// rate of the interface at `time` or the platform failed to send it.
+    pub fn send(&self, frame: &Frame<'_>, time: &Duration) -> Result<usize, InterfaceError> {
+        if frame.payload.len() > self.mtu {
             return Err(InterfaceError::InsufficientBuffer);
         }
+        if !self.shaper.take(frame, time) {
+            router_debug!("Dropping frame exceeding rate of interface");
+            return Err(InterfaceError::RateLimited);
+        }
 
         router_trace!("Sending to interface");
-        H::platform_interface_send_unchecked(self.id, buf)
+        H::platform_interface_send_unchecked(self.id, frame)
+    }
+
+    /// Whether a frame of size `len` can be sent at `time` without exceeding
+    /// the rate of the interface.
+    pub fn ready(&self, len: PayloadSize, time: &Duration) -> bool {
+        self.shaper.ready(len, time)
+    }
+
+    /// The platform-specific ID of the interface.
+    pub fn id(&self) -> NetworkInterfaceId {
+        self.id
     }
 
-    /// Receives data from the interface.
-    pub fn receive<'a>(&self, buf: &'a mut [u8]) -> Result<&'a [u8], InterfaceError> {
+    /// Counters of the transmit shaper.
+    pub fn shaper_stats(&self) -> ShaperStats {
+        self.shaper.stats.get()
+    }
+
+    /// Receives a frame from the interface.
+    pub fn receive<'a>(&self, buf: &'a mut [u8]) -> Result<Frame<'a>, InterfaceError> {
         if buf.len() < self.mtu {
             return Err(InterfaceError::InsufficientBuffer);
         }
@@ -65,11 +93,15 @@ impl<H: PlatformNetworkInterface> NetworkInterface<H> {
 }
 
 /// Platform-specific network interface type.
+///
+/// Multiple virtual links may share a single network interface, so
+/// implementations must transmit the ID of the virtual link together with the
+/// payload. [`Frame::encode`] provides a simple encoding for this.
 pub trait PlatformNetworkInterface {
     /// Send something to the network and report how long it took.
     fn platform_interface_send_unchecked(
         id: NetworkInterfaceId,
-        buffer: &[u8],
+        frame: &Frame<'_>,
     ) -> Result<usize, InterfaceError>;
 
     /// Receive something from the network and report the virtual link id and
@@ -77,7 +109,122 @@ pub trait PlatformNetworkInterface {
     fn platform_interface_receive_unchecked(
         id: NetworkInterfaceId,
         buffer: &'_ mut [u8],
-    ) -> Result<&'_ [u8], InterfaceError>;
+    ) -> Result<Frame<'_>, InterfaceError>;
+
+    /// Whether the ID of the virtual link can be transmitted by this type of
+    /// interface.
+    ///
+    /// The default allows all IDs that can be encoded by [`Frame::encode`].
+    fn supports_virtual_link(vl: &VirtualLinkId) -> bool {
+        u16::try_from(vl.into_inner()).is_ok()
+    }
+}
+
+/// A message of a virtual link.
+///
+/// On the wire, the ID of the virtual link is encoded as a big-endian `u16`
+/// followed by a byte of flags and the optional sequence number in front of the
+/// payload. Virtual links that are transmitted using this format must therefore
+/// have IDs that are at-most `u16::MAX`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'p> {
+    /// The virtual link the payload belongs to.
+    pub vl: VirtualLinkId,
+    /// Whether the payload is valid.
+    /// Samples that are older than the refresh period of their sampling port
+    /// are invalid.
+    pub valid: bool,
+    /// Priority of the virtual link.
+    /// Interfaces may use this to prioritize the frame on the network. It is
+    /// not part of the encoded frame.
+    pub priority: u8,
+    /// Sequence number of the frame on the network.
+    /// Only frames of virtual links that use sequence numbers have one.
+    pub sequence: Option<u8>,
+    /// The payload.
+    pub payload: &'p [u8],
+}
+
+impl<'p> Frame<'p> {
+    /// Length of the header in front of the payload.
+    pub const HEADER_LEN: usize = size_of::<u16>() + size_of::<u8>();
+
+    /// Length of the header including the sequence number.
+    pub const MAX_HEADER_LEN: usize = Self::HEADER_LEN + size_of::<u8>();
+
+    const FLAG_INVALID: u8 = 0x01;
+
+    const FLAG_SEQUENCE: u8 = 0x02;
+
+    /// Creates a new frame containing a valid payload.
+    pub const fn new(vl: VirtualLinkId, payload: &'p [u8]) -> Self {
+        Self {
+            vl,
+            valid: true,
+            priority: 0,
+            sequence: None,
+            payload,
+        }
+    }
+
+    /// Length of the header of the encoded frame.
+    const fn header_len(&self) -> usize {
+        match self.sequence {
+            Some(_) => Self::MAX_HEADER_LEN,
+            None => Self::HEADER_LEN,
+        }
+    }
+
+    /// Length of the encoded frame.
+    pub const fn encoded_len(&self) -> usize {
+        self.header_len() + self.payload.len()
+    }
+
+    /// Encodes the frame into `buf` and returns the encoded portion of `buf`.
+    ///
+    /// # Errors
+    /// Returns an error if `buf` is too small or the ID of the virtual link is
+    /// too large to be encoded.
+    pub fn encode<'a>(&self, buf: &'a mut [u8]) -> Result<&'a [u8], InterfaceError> {
+        let vl = u16::try_from(self.vl.into_inner()).or(Err(InterfaceError::InvalidData))?;
+        let buf = buf
+            .get_mut(..self.encoded_len())
+            .ok_or(InterfaceError::InsufficientBuffer)?;
+        let (header, payload) = buf.split_at_mut(self.header_len());
+        header[..2].copy_from_slice(&vl.to_be_bytes());
+        header[2] = if self.valid { 0 } else { Self::FLAG_INVALID };
+        if let Some(sequence) = self.sequence {
+            header[2] |= Self::FLAG_SEQUENCE;
+            header[3] = sequence;
+        }
+        payload.copy_from_slice(self.payload);
+        Ok(buf)
+    }
+
+    /// Decodes a frame from `buf`.
+    ///
+    /// # Errors
+    /// Returns an error if `buf` is too short to contain the header.
+    pub fn decode(buf: &'p [u8]) -> Result<Self, InterfaceError> {
+        if buf.len() < Self::HEADER_LEN {
+            return Err(InterfaceError::InvalidData);
+        }
+        let flags = buf[2];
+        let (header, payload) = if flags & Self::FLAG_SEQUENCE == 0 {
+            buf.split_at(Self::HEADER_LEN)
+        } else {
+            buf.split_at_checked(Self::MAX_HEADER_LEN)
+                .ok_or(InterfaceError::InvalidData)?
+        };
+        let vl = u16::from_be_bytes([header[0], header[1]]);
+        Ok(Self {
+            vl: VirtualLinkId::from(vl),
+            valid: flags & Self::FLAG_INVALID == 0,
+            priority: 0,
+            sequence: header.get(Self::HEADER_LEN).copied(),
+            payload,
+        })
+    }
 }
 
 /// Creates a network interface id.
@@ -107,17 +254,116 @@ where
             _p: PhantomData,
             id: T::create_network_interface_id(cfg)?,
             mtu: cfg.mtu,
+            shaper: Shaper::new(cfg.rate, cfg.mtu),
         })
     }
 }
 
-const MAX_SOCKET_NAME: usize = 50;
+/// Counters of the transmit shaper of a network interface.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShaperStats {
+    /// Frames that were sent.
+    pub sent: usize,
+    /// Times a frame was left in its source because the rate would have been
+    /// exceeded.
+    pub deferred: usize,
+    /// Frames that were dropped because the rate would have been exceeded.
+    pub dropped: usize,
+}
+
+/// Token bucket limiting the rate at which frames are sent.
+///
+/// The bucket holds enough tokens for a single frame of maximum size and is
+/// refilled at the rate of the interface. The tokens are counted in bit
+/// nanoseconds to avoid losing fractions of bits between refills.
+#[derive(Debug, Clone)]
+struct Shaper {
+    rate: DataRate,
+    capacity: u128,
+    tokens: Cell<u128>,
+    last: Cell<Option<Duration>>,
+    stats: Cell<ShaperStats>,
+}
+
+impl Shaper {
+    const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+    fn new(rate: DataRate, mtu: PayloadSize) -> Self {
+        let capacity = Self::cost(Frame::MAX_HEADER_LEN + mtu);
+        Self {
+            rate,
+            capacity,
+            tokens: Cell::new(capacity),
+            last: Cell::new(None),
+            stats: Cell::default(),
+        }
+    }
+
+    /// Tokens required for sending a frame whose encoding has a size of
+    /// `encoded_len`.
+    fn cost(encoded_len: usize) -> u128 {
+        encoded_len as u128 * 8 * Self::NANOS_PER_SEC
+    }
+
+    /// A rate of zero disables shaping.
+    fn is_unlimited(&self) -> bool {
+        self.rate.as_u64() == 0
+    }
+
+    fn refill(&self, time: &Duration) {
+        let elapsed = self
+            .last
+            .get()
+            .map(|last| time.saturating_sub(last))
+            .unwrap_or_default();
+        let tokens = self.tokens.get() + elapsed.as_nanos() * self.rate.as_u64() as u128;
+        self.tokens.set(tokens.min(self.capacity));
+        self.last.set(Some(*time));
+    }
+
+    fn update_stats(&self, f: impl FnOnce(&mut ShaperStats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    /// Whether any frame with a payload of size `len` can be sent at `time`,
+    /// including a frame with a sequence number.
+    fn ready(&self, len: PayloadSize, time: &Duration) -> bool {
+        if self.is_unlimited() {
+            return true;
+        }
+        self.refill(time);
+        self.tokens.get() >= Self::cost(Frame::MAX_HEADER_LEN + len)
+    }
+
+    fn defer(&self) {
+        self.update_stats(|s| s.deferred += 1);
+    }
+
+    fn take(&self, frame: &Frame<'_>, time: &Duration) -> bool {
+        if !self.is_unlimited() {
+            self.refill(time);
+            let cost = Self::cost(frame.encoded_len());
+            let Some(tokens) = self.tokens.get().checked_sub(cost) else {
+                self.update_stats(|s| s.dropped += 1);
+                return false;
+            };
+            self.tokens.set(tokens);
+        }
+        self.update_stats(|s| s.sent += 1);
+        true
+    }
+}
+
+const MAX_SOCKET_NAME: usize = 128;
 
 /// Configuration for an interface.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct InterfaceConfig {
-    /// UDP source address the socket is bound to.
+    /// Source of the interface, e.g. the UDP address or the path the socket is
+    /// bound to.
     pub source: String<MAX_SOCKET_NAME>,
 
     /// The maximum rate the interface can transmit at.
@@ -127,7 +373,8 @@ pub struct InterfaceConfig {
     /// link.
     pub mtu: PayloadSize,
 
-    /// UDP destination peer
+    /// Destination of the interface, e.g. the UDP address or the path of the
+    /// peer.
     pub destination: String<MAX_SOCKET_NAME>,
 }
 
@@ -144,10 +391,13 @@ impl InterfaceConfig {
 }
 
 impl<H: PlatformNetworkInterface> RouterInput for NetworkInterface<H> {
-    fn receive<'a>(&self, buf: &'a mut [u8]) -> Result<&'a [u8], PortError> {
-        NetworkInterface::receive(self, buf).map_err(|e| {
-            router_debug!("Failed to receive from network interface: {:?}", e);
-            PortError::Receive
+    fn receive<'a>(&self, _vl: &VirtualLinkId, buf: &'a mut [u8]) -> Result<Frame<'a>, PortError> {
+        NetworkInterface::receive(self, buf).map_err(|e| match e {
+            InterfaceError::NoData => PortError::WouldBlock,
+            e => {
+                router_debug!("Failed to receive from network interface: {:?}", e);
+                PortError::Receive
+            }
         })
     }
 
@@ -157,11 +407,21 @@ impl<H: PlatformNetworkInterface> RouterInput for NetworkInterface<H> {
 }
 
 impl<H: PlatformNetworkInterface> RouterOutput for NetworkInterface<H> {
-    fn send(&self, buf: &[u8]) -> Result<(), PortError> {
-        NetworkInterface::send(self, buf).map(|_| ()).map_err(|e| {
-            router_debug!("Failed to send to network interface: {:?}", e);
-            PortError::Send
-        })
+    fn send(&self, frame: &Frame<'_>, time: &Duration) -> Result<(), PortError> {
+        NetworkInterface::send(self, frame, time)
+            .map(|_| ())
+            .map_err(|e| {
+                router_debug!("Failed to send to network interface: {:?}", e);
+                PortError::Send
+            })
+    }
+
+    fn ready(&self, len: PayloadSize, time: &Duration) -> bool {
+        NetworkInterface::ready(self, len, time)
+    }
+
+    fn deferred(&self) {
+        self.shaper.defer()
     }
 
     fn mtu(&self) -> PayloadSize {
@@ -182,6 +442,8 @@ pub enum InterfaceError {
     NotFound,
     /// Sending failed
     SendFailed,
+    /// Sending would exceed the rate of the interface
+    RateLimited,
 }
 
 impl Display for InterfaceError {
@@ -192,6 +454,79 @@ impl Display for InterfaceError {
             Self::InvalidData => write!(f, "Invalid data"),
             Self::NotFound => write!(f, "Interface not found"),
             Self::SendFailed => write!(f, "Send failed"),
+            Self::RateLimited => write!(f, "Rate limit exceeded"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_roundtrip() {
+        let mut buf = [0u8; 8];
+        let frame = Frame::new(VirtualLinkId::from(0x1234u16), &[1, 2, 3]);
+        let encoded = frame.encode(&mut buf).unwrap();
+        assert_eq!(encoded, &[0x12, 0x34, 0, 1, 2, 3]);
+        assert_eq!(Frame::decode(encoded).unwrap(), frame);
+
+        let frame = Frame {
+            valid: false,
+            ..frame
+        };
+        let encoded = frame.encode(&mut buf).unwrap();
+        assert_eq!(encoded, &[0x12, 0x34, 1, 1, 2, 3]);
+        assert_eq!(Frame::decode(encoded).unwrap(), frame);
+
+        let frame = Frame {
+            sequence: Some(7),
+            ..frame
+        };
+        let encoded = frame.encode(&mut buf).unwrap();
+        assert_eq!(encoded, &[0x12, 0x34, 3, 7, 1, 2, 3]);
+        assert_eq!(Frame::decode(encoded).unwrap(), frame);
+        assert_eq!(
+            Frame::decode(&encoded[..3]),
+            Err(InterfaceError::InvalidData)
+        );
+
+        let frame = Frame::new(VirtualLinkId::from(0x1_0000u32), &[1, 2, 3]);
+        assert_eq!(frame.encode(&mut buf), Err(InterfaceError::InvalidData));
+        assert_eq!(Frame::decode(&[0x12]), Err(InterfaceError::InvalidData));
+    }
+
+    #[test]
+    fn shaper_limits_rate() {
+        // One frame with a payload of 6 bytes and a sequence number (80 bits)
+        // every 10 ms
+        let shaper = Shaper::new(DataRate::b(8_000), 6);
+        let frame = Frame {
+            sequence: Some(1),
+            ..Frame::new(VirtualLinkId::from(1u16), &[0; 6])
+        };
+        let start = Duration::from_secs(1);
+        assert!(shaper.take(&frame, &start));
+        assert!(!shaper.ready(6, &start));
+        assert!(!shaper.take(&frame, &(start + Duration::from_millis(5))));
+        // A frame without a sequence number costs one byte less
+        let short = Duration::from_millis(9);
+        assert!(!shaper.ready(6, &(start + short)));
+        assert!(shaper.take(
+            &Frame {
+                sequence: None,
+                ..frame
+            },
+            &(start + short)
+        ));
+        assert!(shaper.ready(6, &(start + short + Duration::from_millis(10))));
+        assert_eq!(
+            shaper.stats.get(),
+            ShaperStats {
+                sent: 2,
+                deferred: 0,
+                dropped: 1,
+            }
+        );
+    }
+}
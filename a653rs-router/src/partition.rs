@@ -6,12 +6,15 @@ use a653rs::{
 };
 
 use crate::{
-    config::VirtualLinksConfig,
+    config::{PortName, RoutingModesConfig, SchedulerConfig, VirtualLinksConfig},
+    modes::ModalRouter,
     prelude::{
-        CreateNetworkInterfaceId, Error, InterfacesConfig, PlatformNetworkInterface, PortsConfig,
+        CreateNetworkInterfaceId, Error, InterfaceName, InterfacesConfig, NetworkInterface,
+        PlatformNetworkInterface, PortsConfig,
     },
     process::{ProcessError, RouterProcess},
     router::{Router, RouterResources},
+    scheduler::Scheduler,
 };
 
 /// Router state.
@@ -26,6 +29,7 @@ where
 {
     resources: RouterResources<H, P, IFS, PORTS>,
     process: RouterProcess<H>,
+    period: Duration,
 }
 
 /// An error occured while starting the router.
@@ -75,6 +79,7 @@ where
                 entry_point,
             )
             .map_err(Error::Process)?,
+            period,
         })
     }
 
@@ -87,13 +92,64 @@ where
         self.process.start().map_err(PartitionError::from)
     }
 
+    /// The network interfaces of the router.
+    pub fn interfaces(&self) -> impl Iterator<Item = (&InterfaceName, &NetworkInterface<P>)> + '_ {
+        self.resources.interfaces()
+    }
+
     /// Call this from your entry-point function via a static variable. This is
     /// a current limitation of a653rs.
     pub fn router<const IN: usize, const OUT: usize, const BUF_LEN: usize>(
         &self,
         virtual_links_cfg: VirtualLinksConfig<IN, OUT>,
+        scheduler_cfg: &SchedulerConfig,
         schedule_start: &Duration,
-    ) -> Result<Router<IN, OUT>, Error> {
-        Router::try_new(virtual_links_cfg, &self.resources, schedule_start)
+    ) -> Result<Router<'_, IN, OUT>, Error> {
+        Router::try_new(
+            virtual_links_cfg,
+            scheduler_cfg,
+            &self.resources,
+            &self.period,
+            schedule_start,
+        )
+    }
+
+    /// Creates a router that can switch between the routing modes of the
+    /// configuration at runtime.
+    ///
+    /// See also [RouterState::router].
+    pub fn modal_router<const IN: usize, const OUT: usize, const BUF_LEN: usize>(
+        &self,
+        virtual_links_cfg: VirtualLinksConfig<IN, OUT>,
+        scheduler_cfg: &SchedulerConfig,
+        modes_cfg: &RoutingModesConfig<IN, OUT>,
+        mode_port: Option<&PortName>,
+        schedule_start: &Duration,
+    ) -> Result<ModalRouter<'_, IN, OUT>, Error> {
+        ModalRouter::try_new(
+            virtual_links_cfg,
+            scheduler_cfg,
+            modes_cfg,
+            mode_port,
+            &self.resources,
+            &self.period,
+            schedule_start,
+        )
+    }
+
+    /// Creates a router that uses a custom scheduler.
+    ///
+    /// See also [RouterState::router].
+    pub fn router_with_scheduler<
+        const IN: usize,
+        const OUT: usize,
+        const BUF_LEN: usize,
+        S: Scheduler,
+    >(
+        &self,
+        virtual_links_cfg: VirtualLinksConfig<IN, OUT>,
+        scheduler: S,
+    ) -> Result<Router<'_, IN, OUT, S>, Error> {
+        Router::with_scheduler(virtual_links_cfg, &self.resources, scheduler)
     }
 }
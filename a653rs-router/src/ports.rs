@@ -2,17 +2,21 @@ use a653rs::{bindings::*, prelude::*};
 use core::{fmt::Display, time::Duration};
 
 use crate::{
-    network::PayloadSize,
+    network::{Frame, PayloadSize},
     router::{RouterInput, RouterOutput},
+    types::VirtualLinkId,
 };
 
 impl<S: ApexSamplingPortP4> RouterInput for SamplingPortDestination<S> {
-    fn receive<'a>(&self, buf: &'a mut [u8]) -> Result<&'a [u8], PortError> {
+    fn receive<'a>(&self, vl: &VirtualLinkId, buf: &'a mut [u8]) -> Result<Frame<'a>, PortError> {
         router_bench!(begin_apex_receive, self.id() as u16);
         let res = self.receive(buf);
         router_bench!(end_apex_receive, self.id() as u16);
-        let (_val, data) = res.map_err(|_e| PortError::Receive)?;
-        Ok(data)
+        let (val, data) = res.map_err(|_e| PortError::Receive)?;
+        Ok(Frame {
+            valid: matches!(val, Validity::Valid),
+            ..Frame::new(*vl, data)
+        })
     }
 
     fn mtu(&self) -> PayloadSize {
@@ -21,9 +25,9 @@ impl<S: ApexSamplingPortP4> RouterInput for SamplingPortDestination<S> {
 }
 
 impl<S: ApexSamplingPortP4> RouterOutput for SamplingPortSource<S> {
-    fn send(&self, buf: &[u8]) -> Result<(), PortError> {
+    fn send(&self, frame: &Frame<'_>, _time: &Duration) -> Result<(), PortError> {
         router_bench!(begin_apex_send, self.id() as u16);
-        let res = self.send(buf);
+        let res = self.send(frame.payload);
         router_bench!(end_apex_send, self.id() as u16);
         res.map_err(|_e| PortError::Send)?;
         Ok(())
@@ -35,7 +39,7 @@ impl<S: ApexSamplingPortP4> RouterOutput for SamplingPortSource<S> {
 }
 
 impl<Q: ApexQueuingPortP4> RouterInput for QueuingPortReceiver<Q> {
-    fn receive<'a>(&self, buf: &'a mut [u8]) -> Result<&'a [u8], PortError> {
+    fn receive<'a>(&self, vl: &VirtualLinkId, buf: &'a mut [u8]) -> Result<Frame<'a>, PortError> {
         const TIMEOUT: SystemTime = SystemTime::Normal(Duration::ZERO);
         router_bench!(begin_apex_send, self.id() as u16);
         let res = self.receive(buf, TIMEOUT);
@@ -44,7 +48,7 @@ impl<Q: ApexQueuingPortP4> RouterInput for QueuingPortReceiver<Q> {
             Error::NotAvailable => PortError::WouldBlock,
             _ => PortError::Receive,
         })?;
-        Ok(buf)
+        Ok(Frame::new(*vl, buf))
     }
 
     fn mtu(&self) -> PayloadSize {
@@ -53,10 +57,10 @@ impl<Q: ApexQueuingPortP4> RouterInput for QueuingPortReceiver<Q> {
 }
 
 impl<Q: ApexQueuingPortP4> RouterOutput for QueuingPortSender<Q> {
-    fn send(&self, buf: &[u8]) -> Result<(), PortError> {
+    fn send(&self, frame: &Frame<'_>, _time: &Duration) -> Result<(), PortError> {
         const TIMEOUT: SystemTime = SystemTime::Normal(Duration::ZERO);
         router_bench!(begin_apex_send, self.id() as u16);
-        let res = self.send(buf, TIMEOUT);
+        let res = self.send(frame.payload, TIMEOUT);
         router_bench!(end_apex_send, self.id() as u16);
         res.map_err(|_e| PortError::Send)?;
         Ok(())
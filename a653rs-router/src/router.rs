@@ -2,13 +2,18 @@
 
 use crate::{
     config::{
-        InterfacesConfig, PortConfig, PortName, PortsConfig, RouterConfigError, VirtualLinksConfig,
+        ForwardingMode, InterfacesConfig, PortConfig, PortName, PortsConfig, RouterConfigError,
+        SchedulerConfig, ValidityPolicy, VirtualLinksConfig,
     },
     error::Error,
-    network::{CreateNetworkInterface, NetworkInterface, PayloadSize, PlatformNetworkInterface},
+    network::{
+        CreateNetworkInterface, Frame, NetworkInterface, PayloadSize, PlatformNetworkInterface,
+    },
     ports::PortError,
     prelude::InterfaceName,
-    scheduler::{DeadlineRrScheduler, ScheduleError, Scheduler, TimeSource},
+    scheduler::{AnyScheduler, ScheduleError, Scheduler, TimeSource},
+    sequence::Sequence,
+    stats::RouterStats,
     types::VirtualLinkId,
 };
 
@@ -19,8 +24,8 @@ use a653rs::{
         StartContext,
     },
 };
-use core::{fmt::Debug, marker::PhantomData, ops::Deref, str::FromStr, time::Duration};
-use heapless::{FnvIndexMap, LinearMap, Vec};
+use core::{fmt::Debug, marker::PhantomData, mem, ops::Deref, ptr, str::FromStr, time::Duration};
+use heapless::{Deque, FnvIndexMap, LinearMap, Vec};
 
 #[derive(Debug)]
 enum Port<H: ApexQueuingPortP4 + ApexSamplingPortP4> {
@@ -127,13 +132,26 @@ where
             net_ifs,
         })
     }
+
+    /// The network interfaces of the router.
+    pub fn interfaces(&self) -> impl Iterator<Item = (&InterfaceName, &NetworkInterface<P>)> + '_ {
+        self.net_ifs.iter()
+    }
+
+    /// The sampling port receiver with the given `name`.
+    pub(crate) fn sampling_input(&self, name: &PortName) -> Option<&dyn RouterInput> {
+        match self.ports.get(name) {
+            Some(Port::SamplingIn(p)) => Some(p),
+            _ => None,
+        }
+    }
 }
 
 /// The router.
 #[derive(Debug, Clone)]
-pub struct Router<'a, const IN: usize, const OUT: usize> {
+pub struct Router<'a, const IN: usize, const OUT: usize, S = AnyScheduler<IN>> {
     routes: RouteTable<'a, IN, OUT>,
-    scheduler: DeadlineRrScheduler<IN>,
+    scheduler: S,
 }
 
 impl<'a, const IN: usize, const OUT: usize> Router<'a, IN, OUT> {
@@ -153,44 +171,123 @@ impl<'a, const IN: usize, const OUT: usize> Router<'a, IN, OUT> {
         const PORTS: usize,
     >(
         virtual_links_cfg: VirtualLinksConfig<IN, OUT>,
+        scheduler_cfg: &SchedulerConfig,
         resources: &'a RouterResources<H, P, IFS, PORTS>,
+        period: &Duration,
         schedule_start: &Duration,
+    ) -> Result<Self, Error> {
+        let scheduler =
+            AnyScheduler::try_new(scheduler_cfg, &virtual_links_cfg, period, schedule_start)?;
+        Self::with_scheduler(virtual_links_cfg, resources, scheduler)
+    }
+}
+
+impl<'a, const IN: usize, const OUT: usize, S: Scheduler> Router<'a, IN, OUT, S> {
+    /// Tries to initialize a new router that forwards the virtual links chosen
+    /// by `scheduler`.
+    ///
+    /// # Errors
+    /// This function will return an error if the configuration was invalid or
+    /// did not match the provided resources.
+    pub fn with_scheduler<
+        H: ApexQueuingPortP4 + ApexSamplingPortP4,
+        P: PlatformNetworkInterface,
+        const IFS: usize,
+        const PORTS: usize,
+    >(
+        virtual_links_cfg: VirtualLinksConfig<IN, OUT>,
+        resources: &'a RouterResources<H, P, IFS, PORTS>,
+        scheduler: S,
     ) -> Result<Self, Error> {
         let routes = RouteTable::<IN, OUT>::build(&virtual_links_cfg, resources)?;
-        let scheduler_cfg: Vec<(VirtualLinkId, Duration), IN> = virtual_links_cfg
-            .into_iter()
-            .map(|(id, cfg)| (*id, cfg.period))
-            .collect();
-        let scheduler = DeadlineRrScheduler::try_new(&scheduler_cfg, schedule_start)?;
-        let router = Self { routes, scheduler };
-        Ok(router)
+        Ok(Self { routes, scheduler })
     }
 
     /// Forwards messages between the hypervisor and the network.
+    ///
+    /// Returns a report on the outcome of sending the message to each
+    /// destination of its virtual link. The virtual link of the message may
+    /// differ from the scheduled virtual link if the source is a network
+    /// interface that is shared by multiple virtual links.
+    /// Messages of different virtual links are reported separately. Reports
+    /// that could not be returned yet are returned by the next calls, before
+    /// another virtual link is scheduled.
+    /// Returns `None` if no virtual link was scheduled, the destinations of the
+    /// virtual link were not ready or the message of a virtual link that
+    /// forwards on update has not changed.
+    ///
+    /// # Errors
+    /// Returns an error if no message could be received from the source of the
+    /// scheduled virtual link.
     pub fn forward<const B: usize, T: TimeSource>(
         &mut self,
         time_source: &T,
-    ) -> Result<Option<VirtualLinkId>, Error> {
+    ) -> Result<Option<ForwardReport<OUT>>, Error> {
+        if let Some(report) = self.routes.pending.pop_front() {
+            return Ok(Some(report));
+        }
         let time = time_source.get_time().map_err(ScheduleError::from)?;
         if let Some(next) = self.scheduler.schedule_next(&time) {
             router_bench!(begin_virtual_link_scheduled, next.0 as u16);
-            let res = self.routes.route::<B>(&next);
+            let res = self.routes.route::<B>(&next, &time);
             router_bench!(end_virtual_link_scheduled, next.0 as u16);
-            res?;
-            Ok(Some(next))
+            res
         } else {
             Ok(None)
         }
     }
+
+    /// Counters of the virtual links, their destinations and the network
+    /// interfaces.
+    pub fn stats(&self) -> &RouterStats<IN, OUT> {
+        &self.routes.stats
+    }
+
+    /// The scheduler of the router.
+    pub fn scheduler(&self) -> &S {
+        &self.scheduler
+    }
+
+    /// Restarts the schedule as if the router was created at `start`.
+    pub fn reset_schedule(&mut self, start: &Duration) {
+        self.scheduler.reset(start)
+    }
+
+    /// Resets all counters of the router to zero.
+    pub fn reset_stats(&mut self) {
+        self.routes.stats.reset()
+    }
+
+    /// Takes the reports that have not been returned by [`Router::forward`]
+    /// yet.
+    pub(crate) fn take_pending(&mut self) -> PendingReports<OUT> {
+        mem::take(&mut self.routes.pending)
+    }
+
+    /// Restarts the router at `start` after it was inactive and returns the
+    /// `pending` reports of the previously active router first.
+    ///
+    /// The counters are reset, so they only cover the time since the restart.
+    pub(crate) fn resume(&mut self, start: &Duration, pending: PendingReports<OUT>) {
+        // The pending reports of a router are handed over to the next router
+        // when it becomes inactive, so there are none to be replaced.
+        self.routes.pending = pending;
+        self.routes.stats.reset();
+        self.scheduler.reset(start);
+    }
 }
 
 /// An input to a virtual link.
 pub trait RouterInput {
-    /// Receives a message and store it into `buf`.
+    /// Receives a message for the virtual link `vl` and store it into `buf`.
+    ///
+    /// Returns the virtual link the message belongs to. Inputs that are shared
+    /// by multiple virtual links may return a message of a virtual link other
+    /// than `vl`.
     ///
     /// # Errors
     /// May return an error if receiving the message failed.
-    fn receive<'a>(&self, buf: &'a mut [u8]) -> Result<&'a [u8], PortError>;
+    fn receive<'a>(&self, vl: &VirtualLinkId, buf: &'a mut [u8]) -> Result<Frame<'a>, PortError>;
 
     /// Maximum transfer unit
     fn mtu(&self) -> PayloadSize;
@@ -198,43 +295,407 @@ pub trait RouterInput {
 
 /// An output from a virtual link.
 pub trait RouterOutput {
-    /// Sends `buf` to a virtual link on this `Output`.
+    /// Sends a message of a virtual link on this `Output` at `time`.
+    fn send(&self, frame: &Frame<'_>, time: &Duration) -> Result<(), PortError>;
+
+    /// Whether a message of size `len` can be sent at `time`.
     ///
-    /// Returns a slice to the portion of `buf` that has *not* been transmitted.
-    fn send(&self, buf: &[u8]) -> Result<(), PortError>;
+    /// Messages are left in the source of a virtual link until all of its
+    /// destinations are ready. If the source is shared by multiple virtual
+    /// links, the destinations of all of them have to be ready.
+    fn ready(&self, _len: PayloadSize, _time: &Duration) -> bool {
+        true
+    }
+
+    /// Called when a message of a virtual link is left in its source, because
+    /// this destination of the virtual link was not ready.
+    fn deferred(&self) {}
 
     /// Maximum transfer unit
     fn mtu(&self) -> PayloadSize;
 }
 
+/// Outcome of forwarding messages to the destinations of their virtual link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardReport<const O: usize> {
+    /// The virtual link of the forwarded messages.
+    pub vl: VirtualLinkId,
+    /// Number of forwarded messages.
+    pub messages: usize,
+    /// Number of forwarded bytes.
+    pub bytes: usize,
+    /// Result of sending the messages to each destination.
+    /// If sending failed for multiple messages, the first error is reported.
+    pub destinations: Vec<(PortName, Result<(), PortError>), O>,
+}
+
+impl<const O: usize> ForwardReport<O> {
+    /// Whether the messages were sent to all destinations.
+    pub fn is_ok(&self) -> bool {
+        self.destinations.iter().all(|(_, res)| res.is_ok())
+    }
+
+    /// The destinations the messages could not be sent to.
+    pub fn failures(&self) -> impl Iterator<Item = (&PortName, &PortError)> {
+        self.destinations
+            .iter()
+            .filter_map(|(name, res)| res.as_ref().err().map(|e| (name, e)))
+    }
+
+    /// Adds the outcome of forwarding another message of the same virtual link.
+    ///
+    /// Returns `other` if it belongs to a different virtual link.
+    fn merge(&mut self, other: Self) -> Result<(), Self> {
+        if self.vl != other.vl {
+            return Err(other);
+        }
+        self.messages += other.messages;
+        self.bytes += other.bytes;
+        for ((_, res), (_, other)) in self.destinations.iter_mut().zip(other.destinations) {
+            if res.is_ok() {
+                *res = other;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How messages of a virtual link are forwarded.
+#[derive(Debug, Clone, Copy)]
+struct Policy {
+    max_messages: usize,
+    max_bytes: Option<usize>,
+    validity: ValidityPolicy,
+    forwarding: ForwardingMode,
+    priority: u8,
+    sequence_numbers: bool,
+    skew_max: Option<Duration>,
+}
+
+impl Policy {
+    /// Whether the budget allows forwarding another message of up to `len`
+    /// bytes.
+    fn allows<const O: usize>(&self, report: &ForwardReport<O>, len: PayloadSize) -> bool {
+        report.messages < self.max_messages
+            && self.max_bytes.is_none_or(|b| report.bytes + len <= b)
+    }
+}
+
+type Destinations<'a, const O: usize> = Vec<(PortName, &'a dyn RouterOutput), O>;
+
 type FwdTable<'a, const I: usize, const O: usize> =
-    LinearMap<VirtualLinkId, Vec<&'a dyn RouterOutput, O>, I>;
+    LinearMap<VirtualLinkId, Destinations<'a, O>, I>;
 
 type Inputs<'a, const I: usize> = LinearMap<VirtualLinkId, &'a dyn RouterInput, I>;
 
+type Policies<const I: usize> = LinearMap<VirtualLinkId, Policy, I>;
+
+type Sequences<const I: usize> = LinearMap<VirtualLinkId, Sequence, I>;
+
+/// Maximum number of reports that are held back while routing a virtual link.
+/// Each of the two inputs of a redundant virtual link may return messages of
+/// two virtual links.
+const MAX_PENDING_REPORTS: usize = 3;
+
+/// Reports of messages that were forwarded on another virtual link than the
+/// one whose report was returned.
+type PendingReports<const O: usize> = Deque<ForwardReport<O>, MAX_PENDING_REPORTS>;
+
+/// Outcome of routing the messages of a virtual link.
+enum Routed<const O: usize> {
+    /// Messages were forwarded to the destinations.
+    Forwarded(ForwardReport<O>),
+    /// The destinations were not ready, so the message was left in the source.
+    Deferred,
+    /// The message was not forwarded, because it has not been updated.
+    Unchanged,
+    /// The message was a copy of a message of a redundant virtual link that
+    /// was already forwarded.
+    Discarded,
+}
+
+impl<const O: usize> Routed<O> {
+    fn report(self) -> Option<ForwardReport<O>> {
+        match self {
+            Self::Forwarded(report) => Some(report),
+            _ => None,
+        }
+    }
+}
+
+/// Digests of the messages that were last forwarded on virtual links that
+/// forward on update.
+type Samples<const I: usize> = LinearMap<VirtualLinkId, u64, I>;
+
+/// FNV-1a digest of a message used to detect updates of sampling ports.
+fn digest(frame: &Frame<'_>) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    frame
+        .payload
+        .iter()
+        .chain([u8::from(frame.valid)].iter())
+        .fold(OFFSET, |h, b| (h ^ u64::from(*b)).wrapping_mul(PRIME))
+}
+
 /// The router containing the routing information.
 #[derive(Default, Clone)]
 pub struct RouteTable<'a, const I: usize, const O: usize> {
     inputs: Inputs<'a, I>,
+    /// Inputs from the second network of redundant virtual links.
+    redundant_inputs: Inputs<'a, I>,
     outputs: FwdTable<'a, I, O>,
+    policies: Policies<I>,
+    samples: Samples<I>,
+    sequences: Sequences<I>,
+    pending: PendingReports<O>,
+    stats: RouterStats<I, O>,
 }
 
 impl<'a, const I: usize, const O: usize> RouteTable<'a, I, O> {
     /// Forwards a virtual link from its source to its destinations.
-    fn route<const B: usize>(&self, vl: &VirtualLinkId) -> Result<(), Error> {
+    ///
+    /// Messages are forwarded until the source has no more messages or the
+    /// budget of the virtual link is exhausted.
+    ///
+    /// Returns `None` if the destinations of the virtual link are not ready,
+    /// the virtual link only forwards updated messages and its source has not
+    /// been updated or only copies of already forwarded messages of a
+    /// redundant virtual link were received.
+    fn route<const B: usize>(
+        &mut self,
+        vl: &VirtualLinkId,
+        time: &Duration,
+    ) -> Result<Option<ForwardReport<O>>, Error> {
+        let policy = *self.policies.get(vl).ok_or(RouteError::InvalidVl)?;
+        self.stats.scheduled(vl);
+        let input = *self.inputs.get(vl).ok_or(RouteError::InvalidVl)?;
+        let res = self.drain::<B>(vl, input, &policy, time);
+        let Some(redundant) = self.redundant_inputs.get(vl).copied() else {
+            return res.map(Routed::report);
+        };
+        // Both networks are received from, so neither of them falls behind.
+        // Their destinations are the same, so both have to wait if they are
+        // not ready.
+        if matches!(res, Ok(Routed::Deferred)) {
+            return Ok(None);
+        }
+        match (res, self.drain::<B>(vl, redundant, &policy, time)) {
+            (Ok(Routed::Forwarded(mut report)), Ok(Routed::Forwarded(other))) => {
+                if let Err(other) = report.merge(other) {
+                    self.hold_back(other)?;
+                }
+                Ok(Some(report))
+            }
+            (Ok(Routed::Forwarded(report)), _) | (_, Ok(Routed::Forwarded(report))) => {
+                Ok(Some(report))
+            }
+            (Ok(_), _) | (_, Ok(_)) => Ok(None),
+            (Err(e), Err(_)) => Err(e),
+        }
+    }
+
+    /// Holds back the report of another virtual link until the next call of
+    /// [`Router::forward`].
+    ///
+    /// # Errors
+    /// Returns an error if too many reports are held back already. The
+    /// messages of the dropped report have been forwarded regardless.
+    fn hold_back(&mut self, report: ForwardReport<O>) -> Result<(), Error> {
+        self.pending.push_back(report).map_err(|report| {
+            router_debug!("Dropping report of VL {:?}", report.vl);
+            self.stats.unreported(&report.vl);
+            RouteError::Unreported.into()
+        })
+    }
+
+    /// Forwards messages from one input of a virtual link until it has no more
+    /// messages or the budget of the virtual link is exhausted.
+    ///
+    /// Draining stops at the first message of another virtual link, which is
+    /// reported separately. Copies of already forwarded messages of a
+    /// redundant virtual link are skipped.
+    fn drain<const B: usize>(
+        &mut self,
+        vl: &VirtualLinkId,
+        input: &'a dyn RouterInput,
+        policy: &Policy,
+        time: &Duration,
+    ) -> Result<Routed<O>, Error> {
+        let mut drained: Option<Routed<O>> = None;
+        loop {
+            if let Some(Routed::Forwarded(report)) = &drained {
+                if !policy.allows(report, input.mtu()) {
+                    break;
+                }
+            }
+            match (self.route_message::<B>(vl, input, time), &mut drained) {
+                (Ok(Routed::Forwarded(next)), Some(Routed::Forwarded(report))) => {
+                    if let Err(next) = report.merge(next) {
+                        self.hold_back(next)?;
+                        break;
+                    }
+                }
+                (Ok(Routed::Forwarded(next)), _) => drained = Some(Routed::Forwarded(next)),
+                (Ok(Routed::Discarded), Some(Routed::Forwarded(_))) => {}
+                (Ok(Routed::Discarded), _) => drained = Some(Routed::Discarded),
+                (Ok(routed), None) => return Ok(routed),
+                (Ok(_), Some(_)) => break,
+                (Err(e), drained) => {
+                    self.stats.failed(vl, &e);
+                    if drained.is_none() {
+                        return Err(e);
+                    }
+                    router_trace!("Stopped draining {:?}: {}", vl, e);
+                    break;
+                }
+            }
+        }
+        // Only set once a message was received
+        Ok(drained.unwrap_or(Routed::Discarded))
+    }
+
+    /// Forwards a single message from the source of a virtual link to its
+    /// destinations.
+    ///
+    /// A failure to send to one destination does not prevent sending to the
+    /// remaining destinations.
+    fn route_message<const B: usize>(
+        &mut self,
+        vl: &VirtualLinkId,
+        input: &'a dyn RouterInput,
+        time: &Duration,
+    ) -> Result<Routed<O>, Error> {
         let buf = &mut [0u8; B];
-        let input = self.inputs.get(vl).ok_or(RouteError::InvalidVl)?;
-        let buf = input.receive(buf)?;
-        router_debug!("Received from {vl:?}: {buf:?}");
-        let outs = self.outputs.get(vl).ok_or(RouteError::InvalidVl)?;
-        for out in outs.into_iter() {
-            out.send(buf).map_err(|e| {
-                router_debug!("Failed to route {:?}", vl);
-                e
-            })?;
-            router_debug!("Send to {vl:?}: {buf:?}");
+        if !self.outputs.contains_key(vl) {
+            return Err(RouteError::InvalidVl.into());
+        }
+        // A shared input may return a message of any virtual link that has it as
+        // its source, so the destinations of all of them have to be ready.
+        let ready = self
+            .inputs
+            .iter()
+            .chain(self.redundant_inputs.iter())
+            .filter(|(_, i)| ptr::addr_eq(**i, input))
+            .filter_map(|(v, _)| self.outputs.get(v))
+            .flatten()
+            .all(|(_, out)| out.ready(input.mtu(), time));
+        if !ready {
+            router_trace!("Deferring VL {:?} until its destinations are ready", vl);
+            self.stats.deferred(vl);
+            self.outputs
+                .get(vl)
+                .into_iter()
+                .flatten()
+                .filter(|(_, out)| !out.ready(input.mtu(), time))
+                .for_each(|(_, out)| out.deferred());
+            return Ok(Routed::Deferred);
+        }
+        let mut frame = input.receive(vl, buf)?;
+        router_debug!("Received from {:?}: {:?}", frame.vl, frame.payload);
+        let is_input = |inputs: &Inputs<'a, I>, vl: &VirtualLinkId| {
+            inputs.get(vl).is_some_and(|i| ptr::addr_eq(*i, input))
+        };
+        // Messages from shared inputs may only be forwarded on virtual links that
+        // have the input as their source.
+        let redundant = is_input(&self.redundant_inputs, &frame.vl);
+        if !redundant && !is_input(&self.inputs, &frame.vl) {
+            router_debug!("Dropping message of unknown VL {:?}", frame.vl);
+            let redundant = is_input(&self.redundant_inputs, vl);
+            self.stats.received(vl, redundant);
+            return Err(RouteError::InvalidVl.into());
+        }
+        let vl = frame.vl;
+        self.stats.received(&vl, redundant);
+        let policy = *self.policies.get(&vl).ok_or(RouteError::InvalidVl)?;
+        if policy.sequence_numbers && !self.check_sequence(&vl, frame.sequence, &policy, time)? {
+            return Ok(Routed::Discarded);
+        }
+        if !frame.valid {
+            match policy.validity {
+                ValidityPolicy::Forward => frame.valid = true,
+                ValidityPolicy::Drop => {
+                    router_debug!("Dropping invalid message of VL {:?}", vl);
+                    return Err(RouteError::InvalidMessage.into());
+                }
+                ValidityPolicy::Mark => {}
+            }
+        }
+        frame.priority = policy.priority;
+        let digest = match policy.forwarding {
+            ForwardingMode::Periodic => None,
+            ForwardingMode::OnUpdate => Some(digest(&frame)),
+        };
+        if digest.is_some() && self.samples.get(&vl) == digest.as_ref() {
+            router_trace!("Message of VL {:?} has not been updated", vl);
+            return Ok(Routed::Unchanged);
+        }
+        // Sequence numbers are counted per hop.
+        frame.sequence = match self.sequences.get_mut(&vl) {
+            Some(seq) if policy.sequence_numbers => Some(seq.next()),
+            _ => None,
+        };
+        let outs = self.outputs.get(&vl).ok_or(RouteError::InvalidVl)?;
+        let mut report = ForwardReport {
+            vl,
+            messages: 1,
+            bytes: frame.payload.len(),
+            destinations: Vec::new(),
+        };
+        for (name, out) in outs.iter() {
+            let res = out.send(&frame, time);
+            match &res {
+                Ok(()) => router_debug!("Send to {vl:?}: {:?}", frame.payload),
+                Err(e) => router_debug!("Failed to route {:?} to {}: {}", vl, name.deref(), e),
+            }
+            // There are never more results than destinations.
+            _ = report.destinations.push((name.clone(), res));
+        }
+        // Messages that could not be sent to all destinations are sent again.
+        if let Some(digest) = digest.filter(|_| report.is_ok()) {
+            _ = self.samples.insert(vl, digest);
+        }
+        self.stats.forwarded(&report);
+        Ok(Routed::Forwarded(report))
+    }
+
+    /// Checks the sequence number of a message from the network.
+    ///
+    /// Returns whether the message is forwarded. Copies of messages of a
+    /// redundant virtual link that were already received from the other
+    /// network are discarded without an error.
+    /// Messages from ports have no sequence number and are not checked.
+    ///
+    /// # Errors
+    /// Returns an error if the message is a duplicate of the last message or
+    /// older than it.
+    fn check_sequence(
+        &mut self,
+        vl: &VirtualLinkId,
+        seq: Option<u8>,
+        policy: &Policy,
+        time: &Duration,
+    ) -> Result<bool, Error> {
+        let Some(seq) = seq else {
+            return Ok(true);
+        };
+        let sequence = self.sequences.get_mut(vl).ok_or(RouteError::InvalidVl)?;
+        match sequence.receive(seq, time, policy.skew_max) {
+            Ok(lost) => {
+                self.stats.lost(vl, lost);
+                Ok(true)
+            }
+            Err(e @ RouteError::Duplicate) if policy.skew_max.is_some() => {
+                router_trace!("Discarding redundant copy of VL {:?}", vl);
+                self.stats.out_of_sequence(vl, &e, true);
+                Ok(false)
+            }
+            Err(e) => {
+                router_debug!("Dropping message of VL {:?}: {:?}", vl, e);
+                self.stats.out_of_sequence(vl, &e, false);
+                Err(e.into())
+            }
         }
-        Ok(())
     }
 
     fn build<H, P, const IFS: usize, const PORTS: usize>(
@@ -284,48 +745,98 @@ impl<'a, const I: usize, const O: usize> RouteTable<'a, I, O> {
                     .unwrap_or(Ok(()))?,
             };
         }
+        // Network interfaces transmit the ID of the virtual link, so they may be
+        // shared by multiple virtual links.
+        let is_interface = |name: &PortName| resources.net_ifs.contains_key(name);
         let mut b = &mut StateBuilder::default();
         for (v, cfg) in virtual_links_cfg.into_iter() {
             // Check for multiple uses of same source
-            if virtual_links_cfg
-                .iter()
-                .filter(|(_, c)| c.src == cfg.src)
-                .count()
-                > 1
+            if !is_interface(&cfg.src)
+                && virtual_links_cfg
+                    .iter()
+                    .filter(|(_, c)| c.src == cfg.src)
+                    .count()
+                    > 1
             {
                 return Err(RouterConfigError::Source);
             }
+            // Check if the ID can be transmitted over the network
+            let uses_interface = is_interface(&cfg.src) || cfg.dsts.iter().any(is_interface);
+            if uses_interface && !P::supports_virtual_link(v) {
+                return Err(RouterConfigError::VirtualLink);
+            }
+            // Only queuing port receivers and network interfaces can be drained
+            // of multiple messages
+            let is_queue = matches!(resources.ports.get(&cfg.src), Some(Port::QueuingIn(_)))
+                || is_interface(&cfg.src);
+            if cfg.max_messages == 0 || (cfg.max_messages > 1 && !is_queue) {
+                return Err(RouterConfigError::Schedule);
+            }
+            // Only sampling port receivers return the same message repeatedly
+            let is_sampling = matches!(resources.ports.get(&cfg.src), Some(Port::SamplingIn(_)));
+            if cfg.forwarding == ForwardingMode::OnUpdate && !is_sampling {
+                return Err(RouterConfigError::Schedule);
+            }
+            let policy = Policy {
+                max_messages: cfg.max_messages,
+                max_bytes: cfg.max_bytes,
+                validity: cfg.validity,
+                forwarding: cfg.forwarding,
+                priority: cfg.priority,
+                sequence_numbers: cfg.sequence_numbers || cfg.redundancy.is_some(),
+                skew_max: cfg.redundancy.as_ref().map(|r| r.skew_max),
+            };
             let inp = inputs.get(&cfg.src).ok_or_else(|| {
                 router_debug!("Unknown input: {}", cfg.src.deref());
                 RouterConfigError::Source
             })?;
-            let outs: Result<Vec<_, O>, RouterConfigError> = cfg
+            let outs: Result<Destinations<'a, O>, RouterConfigError> = cfg
                 .dsts
                 .iter()
                 .map(|d| {
                     // Check for multiple uses of same destination
-                    if virtual_links_cfg
-                        .iter()
-                        .flat_map(|(_, c)| c.dsts.iter())
-                        .filter(|d_name| *d_name == d)
-                        .count()
-                        > 1
+                    if !is_interface(d)
+                        && virtual_links_cfg
+                            .iter()
+                            .flat_map(|(_, c)| c.dsts.iter())
+                            .filter(|d_name| *d_name == d)
+                            .count()
+                            > 1
                     {
                         return Err(RouterConfigError::Destination);
                     }
-                    outputs.get(d).ok_or_else(|| {
+                    let out = outputs.get(d).ok_or_else(|| {
                         router_debug!("Unknown output {}", d.deref());
                         RouterConfigError::Destination
-                    })
+                    })?;
+                    Ok((d.clone(), *out))
                 })
-                .map(|d| d.copied())
                 .collect();
-            let outs = outs?;
+            let mut outs = outs?;
+            let mut redundant = None;
+            if let Some(r) = &cfg.redundancy {
+                // Interface `b` is used alongside interface `a`
+                let uses = |name: &PortName| cfg.src == *name || cfg.dsts.contains(name);
+                if !is_interface(&r.a) || !is_interface(&r.b) || !uses(&r.a) || uses(&r.b) {
+                    return Err(RouterConfigError::Interface);
+                }
+                if cfg.src == r.a {
+                    redundant = inputs.get(&r.b).copied();
+                }
+                if cfg.dsts.contains(&r.a) {
+                    let out = *outputs.get(&r.b).ok_or(RouterConfigError::Interface)?;
+                    outs.push((r.b.clone(), out))
+                        .or(Err(RouterConfigError::Storage))?;
+                }
+            }
             b = b
-                .route(v, *inp, &outs)
+                .route(v, *inp, redundant, &outs, policy)
                 .map_err(|_e| RouterConfigError::VirtualLink)?;
         }
-        b.build()
+        Ok(RouteTable {
+            stats: RouterStats::new(virtual_links_cfg)?,
+            ..b.build()?
+        })
     }
 }
 
@@ -335,8 +846,14 @@ impl<'a, const I: usize, const O: usize> Debug for RouteTable<'a, I, O> {
     }
 }
 
-type Routes<'a, const I: usize, const O: usize> =
-    LinearMap<VirtualLinkId, (&'a dyn RouterInput, Vec<&'a dyn RouterOutput, O>), I>;
+type Route<'a, const O: usize> = (
+    &'a dyn RouterInput,
+    Option<&'a dyn RouterInput>,
+    Destinations<'a, O>,
+    Policy,
+);
+
+type Routes<'a, const I: usize, const O: usize> = LinearMap<VirtualLinkId, Route<'a, O>, I>;
 
 /// Builds a new router.
 #[derive(Default)]
@@ -355,7 +872,9 @@ impl<'a, const I: usize, const O: usize> StateBuilder<'a, I, O> {
         &mut self,
         vl: &VirtualLinkId,
         input: &'a dyn RouterInput,
-        outputs: &Vec<&'a dyn RouterOutput, O>,
+        redundant: Option<&'a dyn RouterInput>,
+        outputs: &Destinations<'a, O>,
+        policy: Policy,
     ) -> Result<&mut Self, RouterConfigError> {
         if self.vls.contains_key(vl) {
             return Err(RouterConfigError::VirtualLink);
@@ -363,7 +882,7 @@ impl<'a, const I: usize, const O: usize> StateBuilder<'a, I, O> {
 
         // Check if input and output message sizes match
         let input_msg_size = input.mtu();
-        for outp in outputs.iter() {
+        for (_, outp) in outputs.iter() {
             if outp.mtu() != input_msg_size {
                 return Err(RouterConfigError::Destination);
             }
@@ -371,15 +890,18 @@ impl<'a, const I: usize, const O: usize> StateBuilder<'a, I, O> {
 
         _ = self
             .vls
-            .insert(*vl, (input, outputs.clone()))
+            .insert(*vl, (input, redundant, outputs.clone(), policy))
             .map_err(|_e| RouterConfigError::Storage)?;
         Ok(self)
     }
 
     pub fn build(&self) -> Result<RouteTable<'a, I, O>, RouterConfigError> {
         let mut inputs = Inputs::default();
+        let mut redundant_inputs = Inputs::default();
         let mut outputs = FwdTable::default();
-        for (id, (i, o)) in self.vls.iter() {
+        let mut policies = Policies::default();
+        let mut sequences = Sequences::default();
+        for (id, (i, r, o, p)) in self.vls.iter() {
             if inputs.contains_key(id) {
                 return Err(RouterConfigError::Destination);
             }
@@ -387,11 +909,29 @@ impl<'a, const I: usize, const O: usize> StateBuilder<'a, I, O> {
                 return Err(RouterConfigError::Source);
             }
             _ = inputs.insert(*id, *i).or(Err(RouterConfigError::Storage));
+            if let Some(r) = r {
+                _ = redundant_inputs
+                    .insert(*id, *r)
+                    .or(Err(RouterConfigError::Storage));
+            }
             _ = outputs
                 .insert(*id, o.clone())
                 .or(Err(RouterConfigError::Storage));
+            _ = policies.insert(*id, *p).or(Err(RouterConfigError::Storage));
+            _ = sequences
+                .insert(*id, Sequence::default())
+                .or(Err(RouterConfigError::Storage));
         }
-        Ok(RouteTable::<'a, I, O> { inputs, outputs })
+        Ok(RouteTable::<'a, I, O> {
+            inputs,
+            redundant_inputs,
+            outputs,
+            policies,
+            samples: Samples::default(),
+            sequences,
+            pending: PendingReports::default(),
+            stats: RouterStats::default(),
+        })
     }
 }
 
@@ -400,4 +940,490 @@ impl<'a, const I: usize, const O: usize> StateBuilder<'a, I, O> {
 pub enum RouteError {
     /// Invalid virtual link
     InvalidVl,
+    /// The message was invalid and has been dropped
+    InvalidMessage,
+    /// The message repeated the sequence number of the last message and has
+    /// been dropped
+    Duplicate,
+    /// The message was older than the last message and has been dropped
+    OutOfOrder,
+    /// Too many reports of other virtual links were held back, so a report
+    /// was dropped after its messages were forwarded
+    Unreported,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{RedundancyConfig, VirtualLinkConfig};
+    use core::cell::{Cell, RefCell};
+
+    const MTU: PayloadSize = 8;
+
+    const PERIODIC: Policy = Policy {
+        max_messages: 1,
+        max_bytes: None,
+        validity: ValidityPolicy::Forward,
+        forwarding: ForwardingMode::Periodic,
+        priority: 0,
+        sequence_numbers: false,
+        skew_max: None,
+    };
+
+    /// An input that returns the frames that were queued on it.
+    #[derive(Default)]
+    struct Input {
+        frames: RefCell<Deque<Frame<'static>, 8>>,
+    }
+
+    impl Input {
+        fn push(&self, frame: Frame<'static>) {
+            self.frames.borrow_mut().push_back(frame).unwrap();
+        }
+    }
+
+    impl RouterInput for Input {
+        fn receive<'a>(
+            &self,
+            _vl: &VirtualLinkId,
+            _buf: &'a mut [u8],
+        ) -> Result<Frame<'a>, PortError> {
+            self.frames
+                .borrow_mut()
+                .pop_front()
+                .ok_or(PortError::WouldBlock)
+        }
+
+        fn mtu(&self) -> PayloadSize {
+            MTU
+        }
+    }
+
+    /// An output that records the payloads sent to it.
+    #[derive(Default)]
+    struct Output {
+        busy: Cell<bool>,
+        deferred: Cell<usize>,
+        failing: Cell<bool>,
+        invalid: Cell<usize>,
+        sent: RefCell<Vec<(VirtualLinkId, &'static [u8]), 8>>,
+    }
+
+    impl Output {
+        fn take(&self) -> Vec<(VirtualLinkId, &'static [u8]), 8> {
+            self.sent.take()
+        }
+    }
+
+    impl RouterOutput for Output {
+        fn send(&self, frame: &Frame<'_>, _time: &Duration) -> Result<(), PortError> {
+            if self.failing.get() {
+                return Err(PortError::Send);
+            }
+            // Payloads are only ever taken from the static frames of the inputs
+            let payload = PAYLOADS
+                .iter()
+                .find(|p| **p == frame.payload)
+                .expect("unknown payload");
+            if !frame.valid {
+                self.invalid.set(self.invalid.get() + 1);
+            }
+            self.sent.borrow_mut().push((frame.vl, payload)).unwrap();
+            Ok(())
+        }
+
+        fn ready(&self, _len: PayloadSize, _time: &Duration) -> bool {
+            !self.busy.get()
+        }
+
+        fn deferred(&self) {
+            self.deferred.set(self.deferred.get() + 1);
+        }
+
+        fn mtu(&self) -> PayloadSize {
+            MTU
+        }
+    }
+
+    const PAYLOADS: [&[u8]; 3] = [b"ping", b"pong", b"pang"];
+
+    fn frame(vl: u16, payload: &'static [u8]) -> Frame<'static> {
+        Frame::new(VirtualLinkId::from(vl), payload)
+    }
+
+    /// A virtual link with its named source, redundant source and
+    /// destinations.
+    type Link<'a, 'b> = (
+        u16,
+        (&'b str, &'a Input),
+        Option<(&'b str, &'a Input)>,
+        &'b [(&'b str, &'a Output)],
+        Policy,
+    );
+
+    fn route_table<'a>(links: &[Link<'a, '_>]) -> RouteTable<'a, 4, 4> {
+        let mut builder = StateBuilder::default();
+        let mut cfg = VirtualLinksConfig::<4, 4>::default();
+        for (vl, (src, input), redundant, outputs, policy) in links.iter() {
+            let vl = VirtualLinkId::from(*vl);
+            let outputs: Destinations<'a, 4> = outputs
+                .iter()
+                .map(|(name, out)| {
+                    let out: &dyn RouterOutput = *out;
+                    (PortName::from_str(name).unwrap(), out)
+                })
+                .collect();
+            let redundant_input = redundant.map(|(_, r)| -> &dyn RouterInput { r });
+            _ = builder
+                .route(&vl, *input, redundant_input, &outputs, *policy)
+                .unwrap();
+            let redundancy = redundant.map(|(b, _)| RedundancyConfig {
+                a: PortName::from_str(src).unwrap(),
+                b: PortName::from_str(b).unwrap(),
+                skew_max: policy.skew_max.unwrap_or_default(),
+            });
+            let vl_cfg = VirtualLinkConfig {
+                src: PortName::from_str(src).unwrap(),
+                dsts: outputs.iter().map(|(name, _)| name.clone()).collect(),
+                period: Duration::from_millis(1),
+                max_messages: policy.max_messages,
+                max_bytes: policy.max_bytes,
+                validity: policy.validity,
+                forwarding: policy.forwarding,
+                priority: policy.priority,
+                sequence_numbers: policy.sequence_numbers,
+                redundancy,
+            };
+            _ = cfg.insert(vl, vl_cfg).unwrap();
+        }
+        RouteTable {
+            stats: RouterStats::new(&cfg).unwrap(),
+            ..builder.build().unwrap()
+        }
+    }
+
+    #[test]
+    fn failing_destination_does_not_stop_the_others() {
+        let (src, port_1, port_2) = (Input::default(), Output::default(), Output::default());
+        let mut table = route_table(&[(
+            1,
+            ("Source", &src),
+            None,
+            &[("Port1", &port_1), ("Port2", &port_2)],
+            PERIODIC,
+        )]);
+        let vl = VirtualLinkId::from(1u16);
+        port_1.failing.set(true);
+        src.push(frame(1, b"ping"));
+
+        let report = table.route::<MTU>(&vl, &Duration::ZERO).unwrap().unwrap();
+        assert!(!report.is_ok());
+        let port_1_name = PortName::from_str("Port1").unwrap();
+        assert!(report
+            .failures()
+            .eq([(&port_1_name, &PortError::Send)].into_iter()));
+        assert_eq!(port_2.take(), [(vl, b"ping".as_slice())]);
+        let stats = table.stats.virtual_link(&vl).unwrap();
+        assert_eq!(stats.destinations[&port_1_name].failed, 1);
+        assert_eq!(
+            stats.destinations[&PortName::from_str("Port2").unwrap()].sent,
+            1
+        );
+    }
+
+    #[test]
+    fn validity_policy_drops_or_marks_invalid_messages() {
+        let (src, dst) = (Input::default(), Output::default());
+        let invalid = || Frame {
+            valid: false,
+            ..frame(1, b"ping")
+        };
+        let vl = VirtualLinkId::from(1u16);
+        for (validity, forwarded, marked) in [
+            (ValidityPolicy::Forward, true, false),
+            (ValidityPolicy::Drop, false, false),
+            (ValidityPolicy::Mark, true, true),
+        ] {
+            let policy = Policy {
+                validity,
+                ..PERIODIC
+            };
+            let mut table = route_table(&[(1, ("Source", &src), None, &[("Dest", &dst)], policy)]);
+            src.push(invalid());
+            let res = table.route::<MTU>(&vl, &Duration::ZERO);
+            if forwarded {
+                assert!(res.unwrap().unwrap().is_ok(), "{validity:?}");
+                assert_eq!(dst.take(), [(vl, b"ping".as_slice())], "{validity:?}");
+            } else {
+                assert_eq!(res, Err(RouteError::InvalidMessage.into()));
+                assert!(dst.take().is_empty());
+            }
+            assert_eq!(dst.invalid.take(), usize::from(marked), "{validity:?}");
+        }
+    }
+
+    #[test]
+    fn on_update_forwards_only_changed_or_unsent_messages() {
+        let (src, dst) = (Input::default(), Output::default());
+        let policy = Policy {
+            forwarding: ForwardingMode::OnUpdate,
+            ..PERIODIC
+        };
+        let mut table = route_table(&[(1, ("Source", &src), None, &[("Dest", &dst)], policy)]);
+        let vl = VirtualLinkId::from(1u16);
+        let mut route = |payload| {
+            src.push(frame(1, payload));
+            table.route::<MTU>(&vl, &Duration::ZERO).unwrap()
+        };
+
+        assert!(route(b"ping").unwrap().is_ok());
+        assert!(route(b"ping").is_none());
+        assert!(route(b"pong").unwrap().is_ok());
+        assert_eq!(
+            dst.take(),
+            [(vl, b"ping".as_slice()), (vl, b"pong".as_slice())]
+        );
+
+        // A message that could not be sent is sent again
+        dst.failing.set(true);
+        assert!(!route(b"pang").unwrap().is_ok());
+        dst.failing.set(false);
+        assert!(route(b"pang").unwrap().is_ok());
+        assert!(route(b"pang").is_none());
+        assert_eq!(dst.take(), [(vl, b"pang".as_slice())]);
+    }
+
+    #[test]
+    fn redundant_copy_within_skew_max_is_dropped() {
+        let (a, b, host) = (Input::default(), Input::default(), Output::default());
+        let policy = Policy {
+            sequence_numbers: true,
+            skew_max: Some(Duration::from_millis(1)),
+            ..PERIODIC
+        };
+        let mut table = route_table(&[(
+            1,
+            ("NetA", &a),
+            Some(("NetB", &b)),
+            &[("Host", &host)],
+            policy,
+        )]);
+        let vl = VirtualLinkId::from(1u16);
+        let ping = Frame {
+            sequence: Some(0),
+            ..frame(1, b"ping")
+        };
+        a.push(ping);
+        b.push(ping);
+
+        let report = table.route::<MTU>(&vl, &Duration::ZERO).unwrap().unwrap();
+        assert_eq!(report.messages, 1);
+        assert_eq!(host.take(), [(vl, b"ping".as_slice())]);
+
+        // The copy from B arrives late, but still within skew_max
+        let pong = Frame {
+            sequence: Some(1),
+            ..frame(1, b"pong")
+        };
+        a.push(pong);
+        _ = table.route::<MTU>(&vl, &Duration::from_millis(2)).unwrap();
+        b.push(pong);
+        let time = Duration::from_micros(2_500);
+        assert_eq!(table.route::<MTU>(&vl, &time), Ok(None));
+        assert_eq!(host.take(), [(vl, b"pong".as_slice())]);
+        assert_eq!(table.stats.virtual_link(&vl).unwrap().redundant, 2);
+    }
+
+    #[test]
+    fn redundant_copy_after_skew_max_is_forwarded() {
+        let (a, b, host) = (Input::default(), Input::default(), Output::default());
+        let policy = Policy {
+            sequence_numbers: true,
+            skew_max: Some(Duration::from_millis(1)),
+            ..PERIODIC
+        };
+        let mut table = route_table(&[(
+            1,
+            ("NetA", &a),
+            Some(("NetB", &b)),
+            &[("Host", &host)],
+            policy,
+        )]);
+        let vl = VirtualLinkId::from(1u16);
+        let ping = Frame {
+            sequence: Some(0),
+            ..frame(1, b"ping")
+        };
+        a.push(ping);
+        _ = table.route::<MTU>(&vl, &Duration::ZERO).unwrap().unwrap();
+        assert_eq!(host.take(), [(vl, b"ping".as_slice())]);
+
+        b.push(ping);
+        let report = table.route::<MTU>(&vl, &Duration::from_millis(2)).unwrap();
+        assert_eq!(report.unwrap().messages, 1);
+        assert_eq!(host.take(), [(vl, b"ping".as_slice())]);
+        assert_eq!(table.stats.virtual_link(&vl).unwrap().redundant, 0);
+    }
+
+    #[test]
+    fn reports_that_can_not_be_held_back_are_counted() {
+        let (net, port_1, port_2) = (Input::default(), Output::default(), Output::default());
+        let shared = Policy {
+            max_messages: 4,
+            ..PERIODIC
+        };
+        let mut table = route_table(&[
+            (1, ("Net", &net), None, &[("Port1", &port_1)], shared),
+            (2, ("Net", &net), None, &[("Port2", &port_2)], shared),
+        ]);
+        let vl_1 = VirtualLinkId::from(1u16);
+        // Each route holds back the report of VL 2, but its messages are still
+        // forwarded once the report can not be held back anymore
+        for _ in 0..=MAX_PENDING_REPORTS {
+            net.push(frame(1, b"ping"));
+            net.push(frame(2, b"pang"));
+        }
+        for _ in 0..MAX_PENDING_REPORTS {
+            assert!(table.route::<MTU>(&vl_1, &Duration::ZERO).is_ok());
+        }
+        assert_eq!(
+            table.route::<MTU>(&vl_1, &Duration::ZERO),
+            Err(RouteError::Unreported.into())
+        );
+        assert_eq!(port_1.take().len(), MAX_PENDING_REPORTS + 1);
+        assert_eq!(port_2.take().len(), MAX_PENDING_REPORTS + 1);
+        let stats = table.stats.virtual_link(&VirtualLinkId::from(2u16));
+        assert_eq!(stats.unwrap().unreported, 1);
+    }
+
+    #[test]
+    fn budget_leaves_remaining_messages_queued() {
+        let (queue, port) = (Input::default(), Output::default());
+        let budget = Policy {
+            max_messages: 4,
+            max_bytes: Some(2 * MTU),
+            ..PERIODIC
+        };
+        let mut table = route_table(&[(1, ("Queue", &queue), None, &[("Port", &port)], budget)]);
+        let vl = VirtualLinkId::from(1u16);
+        for _ in 0..6 {
+            queue.push(frame(1, b"ping"));
+        }
+        // Another message of up to the MTU would exceed the byte budget after
+        // the third message.
+        let report = table.route::<MTU>(&vl, &Duration::ZERO).unwrap().unwrap();
+        assert_eq!((report.messages, report.bytes), (3, 12));
+        assert_eq!(port.take().len(), 3);
+        assert_eq!(queue.frames.borrow().len(), 3);
+
+        let report = table.route::<MTU>(&vl, &Duration::ZERO).unwrap().unwrap();
+        assert_eq!(report.messages, 3);
+        assert!(queue.frames.borrow().is_empty());
+
+        let budget = Policy {
+            max_messages: 2,
+            ..PERIODIC
+        };
+        let mut table = route_table(&[(1, ("Queue", &queue), None, &[("Port", &port)], budget)]);
+        for _ in 0..3 {
+            queue.push(frame(1, b"ping"));
+        }
+        let report = table.route::<MTU>(&vl, &Duration::ZERO).unwrap().unwrap();
+        assert_eq!(report.messages, 2);
+        assert_eq!(queue.frames.borrow().len(), 1);
+    }
+
+    #[test]
+    fn shared_input_waits_for_destinations_of_all_its_virtual_links() {
+        let (net, port_1, port_2) = (Input::default(), Output::default(), Output::default());
+        let mut table = route_table(&[
+            (1, ("Net", &net), None, &[("Port1", &port_1)], PERIODIC),
+            (2, ("Net", &net), None, &[("Port2", &port_2)], PERIODIC),
+        ]);
+        let vl_1 = VirtualLinkId::from(1u16);
+        net.push(frame(2, b"ping"));
+        port_2.busy.set(true);
+        assert_eq!(table.route::<MTU>(&vl_1, &Duration::ZERO), Ok(None));
+        assert_eq!(net.frames.borrow().len(), 1);
+        assert_eq!(table.stats.virtual_link(&vl_1).unwrap().deferred, 1);
+        // Only the destinations of the scheduled virtual link count the deferral
+        assert_eq!(port_2.deferred.get(), 0);
+        port_1.busy.set(true);
+        assert_eq!(table.route::<MTU>(&vl_1, &Duration::ZERO), Ok(None));
+        assert_eq!((port_1.deferred.get(), port_2.deferred.get()), (1, 0));
+
+        port_1.busy.set(false);
+        port_2.busy.set(false);
+        let report = table.route::<MTU>(&vl_1, &Duration::ZERO).unwrap().unwrap();
+        assert_eq!(report.vl, VirtualLinkId::from(2u16));
+        assert_eq!(port_2.take().len(), 1);
+    }
+
+    #[test]
+    fn stats_count_messages_per_virtual_link_and_interface() {
+        let (a, b, sensor) = (Input::default(), Input::default(), Input::default());
+        let (host, net_a) = (Output::default(), Output::default());
+        let redundant = Policy {
+            sequence_numbers: true,
+            skew_max: Some(Duration::from_millis(1)),
+            ..PERIODIC
+        };
+        let mut table = route_table(&[
+            (
+                1,
+                ("NetA", &a),
+                Some(("NetB", &b)),
+                &[("Host", &host)],
+                redundant,
+            ),
+            (2, ("Sensor", &sensor), None, &[("NetA", &net_a)], PERIODIC),
+        ]);
+        let (vl_1, vl_2) = (VirtualLinkId::from(1u16), VirtualLinkId::from(2u16));
+        let ping = Frame {
+            sequence: Some(0),
+            ..frame(1, b"ping")
+        };
+        let pong = Frame {
+            sequence: Some(1),
+            ..frame(1, b"pong")
+        };
+        a.push(ping);
+        b.push(ping);
+        b.push(pong);
+        sensor.push(frame(2, b"pang"));
+        // The copy of ping from B is skipped and pong is forwarded with ping
+        let report = table.route::<MTU>(&vl_1, &Duration::ZERO).unwrap();
+        assert_eq!(report.unwrap().messages, 2);
+        assert!(table.route::<MTU>(&vl_1, &Duration::ZERO).is_err());
+        _ = table.route::<MTU>(&vl_2, &Duration::ZERO).unwrap();
+
+        let stats = table.stats.virtual_link(&vl_1).unwrap();
+        assert_eq!(stats.scheduled, 2);
+        assert_eq!((stats.received, stats.redundant_received), (1, 2));
+        assert_eq!((stats.messages, stats.bytes, stats.redundant), (2, 8, 1));
+        assert_eq!(
+            stats.destinations[&PortName::from_str("Host").unwrap()].sent,
+            2
+        );
+        let interface = |name| table.stats.interface(&PortName::from_str(name).unwrap());
+        assert_eq!(interface("NetA").received, 1);
+        assert_eq!(interface("NetA").sent, 1);
+        assert_eq!(interface("NetB").received, 2);
+        assert_eq!(interface("Host").sent, 2);
+        assert_eq!(interface("Sensor").received, 1);
+
+        table.stats.reset();
+        let stats = table.stats.virtual_link(&vl_1).unwrap();
+        let name = |name| PortName::from_str(name).unwrap();
+        assert_eq!(stats.source, name("NetA"));
+        assert_eq!(stats.redundant_source, Some(name("NetB")));
+        assert_eq!((stats.scheduled, stats.received, stats.messages), (0, 0, 0));
+        assert_eq!(
+            stats.destinations[&PortName::from_str("Host").unwrap()].sent,
+            0
+        );
+        let interface = table.stats.interface(&PortName::from_str("NetB").unwrap());
+        assert_eq!(interface.received, 0);
+        assert_eq!(table.stats.virtual_link(&vl_2).unwrap().messages, 0);
+    }
 }
@@ -1,4 +1,7 @@
-use crate::{config::RouterConfigError, types::VirtualLinkId};
+use crate::{
+    config::{RouterConfigError, SchedulerConfig, VirtualLinksConfig},
+    types::VirtualLinkId,
+};
 
 use a653rs::prelude::{ApexTimeP4Ext, SystemTime};
 use core::{
@@ -16,6 +19,12 @@ pub trait Scheduler {
     /// Get the next scheduled virtual link, if one is to be scheduled at the
     /// current time.
     fn schedule_next(&mut self, current_time: &Duration) -> Option<VirtualLinkId>;
+
+    /// Restarts the schedule as if the scheduler was created at `start`.
+    ///
+    /// Does nothing by default, for schedulers that do not depend on their
+    /// start time.
+    fn reset(&mut self, _start: &Duration) {}
 }
 
 /// The deadline of a window in which a virtual link is to be scheduled next.
@@ -97,18 +106,281 @@ impl<const SLOTS: usize> Scheduler for DeadlineRrScheduler<SLOTS> {
         }
         None
     }
+
+    fn reset(&mut self, start: &Duration) {
+        self.last_window = 0;
+        for w in self.windows.iter_mut() {
+            w.last = *start + w.period;
+        }
+    }
 }
 
-/// A slot inside the round-robin scheduler.
+/// Maximum number of slots in the table of a [`StaticTableScheduler`].
+pub const MAX_TABLE_SLOTS: usize = 32;
+
+/// A slot of a [`StaticTableScheduler`].
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, Clone)]
-pub struct DeadlineRrSlot {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableSlot {
+    /// Offset of the slot from the start of the major frame.
+    pub offset: Duration,
+
     /// Virtual link to schedule in this slot.
     pub vl: VirtualLinkId,
+}
+
+/// A time-triggered scheduler that forwards virtual links at fixed offsets
+/// inside a cyclic major frame.
+///
+/// The first major frame starts at the time the scheduler is created. Each slot
+/// is scheduled once it is due. If the next slot is already due as well, the
+/// slot has been missed and is skipped in favor of the latest due slot.
+#[derive(Debug, Clone)]
+pub struct StaticTableScheduler<const SLOTS: usize> {
+    major_frame: Duration,
+    slots: Vec<TableSlot, SLOTS>,
+    start: Duration,
+    /// Number of slots that have been scheduled or missed since the start.
+    position: u64,
+    missed: u64,
+}
+
+impl<const SLOTS: usize> StaticTableScheduler<SLOTS> {
+    /// Constructs a new StaticTableScheduler.
+    ///
+    /// # Errors
+    /// Returns an error if the table is empty or too large, the major frame is
+    /// zero or the offsets are not strictly increasing and inside the major
+    /// frame.
+    pub fn try_new(
+        major_frame: Duration,
+        slots: &[TableSlot],
+        start: &Duration,
+    ) -> Result<Self, RouterConfigError> {
+        if major_frame.is_zero() || slots.is_empty() {
+            return Err(RouterConfigError::Schedule);
+        }
+        let ordered = slots.windows(2).all(|w| w[0].offset < w[1].offset);
+        let inside = slots.iter().all(|s| s.offset < major_frame);
+        if !ordered || !inside {
+            return Err(RouterConfigError::Schedule);
+        }
+        Ok(Self {
+            major_frame,
+            slots: Vec::from_slice(slots).or(Err(RouterConfigError::Storage))?,
+            start: *start,
+            position: 0,
+            missed: 0,
+        })
+    }
+
+    /// Number of slots that have been missed since the start.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// Position of the latest slot that is due at `current_time`.
+    fn latest_due(&self, current_time: &Duration) -> Option<u64> {
+        let elapsed = current_time.checked_sub(self.start)?.as_nanos();
+        let major_frame = self.major_frame.as_nanos();
+        let frame = (elapsed / major_frame) as u64;
+        let offset = elapsed % major_frame;
+        let slots = self.slots.len() as u64;
+        match self
+            .slots
+            .iter()
+            .rposition(|s| s.offset.as_nanos() <= offset)
+        {
+            Some(i) => Some(frame * slots + i as u64),
+            None => (frame * slots).checked_sub(1),
+        }
+    }
+}
+
+impl<const SLOTS: usize> Scheduler for StaticTableScheduler<SLOTS> {
+    fn schedule_next(&mut self, current_time: &Duration) -> Option<VirtualLinkId> {
+        let latest = self
+            .latest_due(current_time)
+            .filter(|l| *l >= self.position)?;
+        let missed = latest - self.position;
+        if missed > 0 {
+            router_debug!("Missed {} slots of the schedule table", missed);
+            self.missed += missed;
+        }
+        self.position = latest + 1;
+        let slot = self.slots[(latest % self.slots.len() as u64) as usize];
+        router_trace!("Scheduled VL {}", slot.vl);
+        Some(slot.vl)
+    }
+
+    fn reset(&mut self, start: &Duration) {
+        self.start = *start;
+        self.position = 0;
+        self.missed = 0;
+    }
+}
+
+/// A virtual link of the [`PriorityScheduler`].
+#[derive(Debug, Copy, Clone)]
+struct PriorityWindow {
+    window: Window,
+    priority: u8,
+}
+
+impl PriorityWindow {
+    fn deadline(&self) -> Duration {
+        self.window.last + self.window.period
+    }
+
+    /// Priority raised by one for each `aging` that passed since the deadline.
+    fn effective_priority(&self, current_time: &Duration, aging: Option<Duration>) -> u64 {
+        let overdue = current_time.saturating_sub(self.deadline()).as_nanos();
+        let raised = aging
+            .filter(|a| !a.is_zero())
+            .map_or(0, |a| (overdue / a.as_nanos()) as u64);
+        u64::from(self.priority).saturating_add(raised)
+    }
+}
 
-    /// Periodic after which to schedule this slot again after the last time it
-    /// has been scheduled.
-    pub period: Duration,
+/// A scheduler that forwards the due virtual link with the highest priority.
+///
+/// Virtual links of the same priority are ordered by their deadline. With
+/// aging, the priority of a virtual link rises while it is overdue, so virtual
+/// links of low priority are not starved by those of high priority.
+#[derive(Default, Debug, Clone)]
+pub struct PriorityScheduler<const SLOTS: usize> {
+    windows: Vec<PriorityWindow, SLOTS>,
+    aging: Option<Duration>,
+}
+
+impl<const SLOTS: usize> PriorityScheduler<SLOTS> {
+    /// Constructs a new PriorityScheduler from the period and priority of
+    /// each virtual link.
+    ///
+    /// # Errors
+    /// Returns an error if there are more virtual links than slots.
+    pub fn try_new(
+        vls: &[(VirtualLinkId, Duration, u8)],
+        aging: Option<Duration>,
+        start: &Duration,
+    ) -> Result<Self, RouterConfigError> {
+        let windows = vls
+            .iter()
+            .map(|(vl, period, priority)| PriorityWindow {
+                window: Window {
+                    vl: *vl,
+                    period: *period,
+                    last: *start + *period,
+                },
+                priority: *priority,
+            })
+            .collect::<Vec<_, SLOTS>>();
+        if windows.len() != vls.len() {
+            return Err(RouterConfigError::Storage);
+        }
+        Ok(Self { windows, aging })
+    }
+}
+
+impl<const SLOTS: usize> Scheduler for PriorityScheduler<SLOTS> {
+    fn schedule_next(&mut self, current_time: &Duration) -> Option<VirtualLinkId> {
+        let aging = self.aging;
+        let next = self
+            .windows
+            .iter_mut()
+            .filter(|w| w.window.is_due(current_time))
+            .min_by_key(|w| {
+                (
+                    u64::MAX - w.effective_priority(current_time, aging),
+                    w.deadline(),
+                )
+            })?;
+        next.window.last = *current_time;
+        router_trace!("Scheduled VL {}", next.window.vl);
+        Some(next.window.vl)
+    }
+
+    fn reset(&mut self, start: &Duration) {
+        for w in self.windows.iter_mut() {
+            w.window.last = *start + w.window.period;
+        }
+    }
+}
+
+/// A scheduler that is selected by a [`SchedulerConfig`].
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum AnyScheduler<const SLOTS: usize> {
+    /// See [`DeadlineRrScheduler`].
+    DeadlineRr(DeadlineRrScheduler<SLOTS>),
+    /// See [`StaticTableScheduler`].
+    StaticTable(StaticTableScheduler<MAX_TABLE_SLOTS>),
+    /// See [`PriorityScheduler`].
+    Priority(PriorityScheduler<SLOTS>),
+}
+
+impl<const SLOTS: usize> AnyScheduler<SLOTS> {
+    /// Constructs the scheduler selected by `cfg` for the virtual links `vls`
+    /// of a router partition with the given `period`.
+    ///
+    /// # Errors
+    /// Returns an error if the scheduler configuration does not match the
+    /// virtual links or the period of the partition.
+    pub fn try_new<const O: usize>(
+        cfg: &SchedulerConfig,
+        vls: &VirtualLinksConfig<SLOTS, O>,
+        period: &Duration,
+        start: &Duration,
+    ) -> Result<Self, RouterConfigError> {
+        match cfg {
+            SchedulerConfig::DeadlineRr => {
+                let periods: Vec<(VirtualLinkId, Duration), SLOTS> =
+                    vls.iter().map(|(id, cfg)| (*id, cfg.period)).collect();
+                DeadlineRrScheduler::try_new(&periods, start).map(Self::DeadlineRr)
+            }
+            SchedulerConfig::StaticTable { major_frame, slots } => {
+                if slots.iter().any(|s| !vls.contains_key(&s.vl)) {
+                    return Err(RouterConfigError::VirtualLink);
+                }
+                if !is_aligned(major_frame, period) {
+                    return Err(RouterConfigError::Schedule);
+                }
+                StaticTableScheduler::try_new(*major_frame, slots, start).map(Self::StaticTable)
+            }
+            SchedulerConfig::Priority { aging } => {
+                let vls: Vec<(VirtualLinkId, Duration, u8), SLOTS> = vls
+                    .iter()
+                    .map(|(id, cfg)| (*id, cfg.period, cfg.priority))
+                    .collect();
+                PriorityScheduler::try_new(&vls, *aging, start).map(Self::Priority)
+            }
+        }
+    }
+}
+
+impl<const SLOTS: usize> Scheduler for AnyScheduler<SLOTS> {
+    fn schedule_next(&mut self, current_time: &Duration) -> Option<VirtualLinkId> {
+        match self {
+            Self::DeadlineRr(s) => s.schedule_next(current_time),
+            Self::StaticTable(s) => s.schedule_next(current_time),
+            Self::Priority(s) => s.schedule_next(current_time),
+        }
+    }
+
+    fn reset(&mut self, start: &Duration) {
+        match self {
+            Self::DeadlineRr(s) => s.reset(start),
+            Self::StaticTable(s) => s.reset(start),
+            Self::Priority(s) => s.reset(start),
+        }
+    }
+}
+
+/// Whether the `major_frame` of a schedule table is a multiple of the `period`
+/// of the router partition.
+pub(crate) fn is_aligned(major_frame: &Duration, period: &Duration) -> bool {
+    let period = period.as_nanos();
+    period != 0 && major_frame.as_nanos().is_multiple_of(period)
 }
 
 /// Source for the system time.
@@ -157,3 +429,106 @@ impl Display for ScheduleError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PortName, VirtualLinkConfig};
+    use core::str::FromStr;
+
+    #[test]
+    fn static_table_schedules_slots() {
+        let slots = [
+            TableSlot {
+                offset: Duration::ZERO,
+                vl: VirtualLinkId::from(1u16),
+            },
+            TableSlot {
+                offset: Duration::from_millis(10),
+                vl: VirtualLinkId::from(2u16),
+            },
+        ];
+        let start = Duration::from_secs(1);
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut s =
+            StaticTableScheduler::<2>::try_new(Duration::from_millis(20), &slots, &start).unwrap();
+        assert_eq!(s.schedule_next(&ms(0)), Some(VirtualLinkId::from(1u16)));
+        assert_eq!(s.schedule_next(&ms(5)), None);
+        assert_eq!(s.schedule_next(&ms(10)), Some(VirtualLinkId::from(2u16)));
+        assert_eq!(s.schedule_next(&ms(21)), Some(VirtualLinkId::from(1u16)));
+        assert_eq!(s.missed(), 0);
+        // Skips VL 2 at 30 ms and VL 1 at 40 ms
+        assert_eq!(s.schedule_next(&ms(55)), Some(VirtualLinkId::from(2u16)));
+        assert_eq!(s.missed(), 2);
+        s.reset(&ms(100));
+        assert_eq!(s.missed(), 0);
+        assert_eq!(s.schedule_next(&ms(100)), Some(VirtualLinkId::from(1u16)));
+
+        let unordered = [slots[1], slots[0]];
+        assert!(
+            StaticTableScheduler::<2>::try_new(Duration::from_millis(20), &unordered, &start)
+                .is_err()
+        );
+        assert!(
+            StaticTableScheduler::<2>::try_new(Duration::from_millis(10), &slots, &start).is_err()
+        );
+    }
+
+    #[test]
+    fn static_table_must_align_with_partition_period() {
+        let vl = VirtualLinkId::from(1u16);
+        let mut vls = VirtualLinksConfig::<2, 2>::default();
+        _ = vls
+            .insert(
+                vl,
+                VirtualLinkConfig {
+                    src: PortName::from_str("Sensor").unwrap(),
+                    dsts: Default::default(),
+                    period: Duration::from_millis(10),
+                    max_messages: 1,
+                    max_bytes: None,
+                    validity: Default::default(),
+                    forwarding: Default::default(),
+                    priority: 0,
+                    sequence_numbers: false,
+                    redundancy: None,
+                },
+            )
+            .unwrap();
+        let cfg = SchedulerConfig::StaticTable {
+            major_frame: Duration::from_millis(20),
+            slots: Vec::from_slice(&[TableSlot {
+                offset: Duration::ZERO,
+                vl,
+            }])
+            .unwrap(),
+        };
+        let start = Duration::ZERO;
+        let try_new =
+            |period| AnyScheduler::try_new(&cfg, &vls, &Duration::from_millis(period), &start);
+        assert!(try_new(10).is_ok());
+        assert!(matches!(try_new(15), Err(RouterConfigError::Schedule)));
+        assert!(matches!(try_new(0), Err(RouterConfigError::Schedule)));
+    }
+
+    #[test]
+    fn priority_scheduler_ages_low_priority() {
+        let low = VirtualLinkId::from(1u16);
+        let high = VirtualLinkId::from(2u16);
+        let period = Duration::from_millis(10);
+        let vls = [(low, period, 0), (high, period, 1)];
+        let start = Duration::ZERO;
+        let ms = Duration::from_millis;
+
+        let mut s = PriorityScheduler::<2>::try_new(&vls, None, &start).unwrap();
+        assert_eq!(s.schedule_next(&ms(5)), None);
+        assert_eq!(s.schedule_next(&ms(20)), Some(high));
+        assert_eq!(s.schedule_next(&ms(30)), Some(high));
+        assert_eq!(s.schedule_next(&ms(30)), Some(low));
+
+        let mut s = PriorityScheduler::<2>::try_new(&vls, Some(ms(5)), &start).unwrap();
+        assert_eq!(s.schedule_next(&ms(20)), Some(high));
+        // VL 1 has been overdue for 10 ms and has aged past VL 2
+        assert_eq!(s.schedule_next(&ms(30)), Some(low));
+    }
+}
@@ -0,0 +1,120 @@
+//! Sequence numbers
+
+use crate::router::RouteError;
+use core::time::Duration;
+
+/// Number of sequence numbers a frame may skip ahead of the last accepted one.
+/// Frames that are further ahead are treated as older than the last accepted
+/// frame.
+const WINDOW: u8 = u8::MAX / 2;
+
+/// Sequence numbers of a virtual link between two routers.
+///
+/// Like the integrity checking of ARINC 664 P7, the first frame after a
+/// restart has the sequence number 0 and the following frames count from 1 to
+/// 255 and wrap around to 1.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Sequence {
+    sent: Option<u8>,
+    received: Option<(u8, Duration)>,
+}
+
+impl Sequence {
+    /// The sequence number of the next frame that is sent.
+    pub(crate) fn next(&mut self) -> u8 {
+        let next = self.sent.map_or(0, successor);
+        self.sent = Some(next);
+        next
+    }
+
+    /// Checks the sequence number of a received frame and returns the number
+    /// of frames that were lost since the last accepted frame.
+    ///
+    /// A sequence number of 0 restarts the sequence, unless it repeats the
+    /// last accepted frame. If `skew_max` has passed
+    /// since the last accepted frame, the sequence is restarted as well.
+    ///
+    /// # Errors
+    /// Returns an error if the frame is a duplicate of the last accepted frame
+    /// or older than it.
+    pub(crate) fn receive(
+        &mut self,
+        seq: u8,
+        time: &Duration,
+        skew_max: Option<Duration>,
+    ) -> Result<usize, RouteError> {
+        let last = self
+            .received
+            .filter(|(_, at)| skew_max.is_none_or(|skew| time.saturating_sub(*at) <= skew));
+        let lost = match last {
+            Some((last, _)) if seq == last => return Err(RouteError::Duplicate),
+            Some((last, _)) if seq != 0 => match distance(last, seq) {
+                d if (1..=WINDOW).contains(&d) => d - 1,
+                _ => return Err(RouteError::OutOfOrder),
+            },
+            _ => 0,
+        };
+        self.received = Some((seq, *time));
+        Ok(lost.into())
+    }
+}
+
+fn successor(seq: u8) -> u8 {
+    seq.checked_add(1).unwrap_or(1)
+}
+
+/// Number of increments from `from` to `to`.
+/// 0 and 255 are both followed by 1, so they are equivalent.
+fn distance(from: u8, to: u8) -> u8 {
+    let cycle = u16::from(u8::MAX);
+    let d = (u16::from(to) % cycle + cycle - u16::from(from) % cycle) % cycle;
+    d as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_detects_lost_duplicate_and_old_frames() {
+        let mut tx = Sequence::default();
+        assert_eq!(tx.next(), 0);
+        assert_eq!(tx.next(), 1);
+        let mut tx = Sequence {
+            sent: Some(254),
+            ..tx
+        };
+        assert_eq!(tx.next(), 255);
+        assert_eq!(tx.next(), 1);
+
+        let mut rx = Sequence::default();
+        let now = Duration::ZERO;
+        let receive = |rx: &mut Sequence, seq| rx.receive(seq, &now, None);
+        assert_eq!(receive(&mut rx, 0), Ok(0));
+        assert_eq!(receive(&mut rx, 0), Err(RouteError::Duplicate));
+        assert_eq!(receive(&mut rx, 1), Ok(0));
+        assert_eq!(receive(&mut rx, 1), Err(RouteError::Duplicate));
+        assert_eq!(receive(&mut rx, 4), Ok(2));
+        assert_eq!(receive(&mut rx, 3), Err(RouteError::OutOfOrder));
+        assert_eq!(receive(&mut rx, 100), Ok(95));
+        assert_eq!(receive(&mut rx, 200), Ok(99));
+        assert_eq!(receive(&mut rx, 254), Ok(53));
+        assert_eq!(receive(&mut rx, 2), Ok(2));
+        // The sender restarted
+        assert_eq!(receive(&mut rx, 0), Ok(0));
+        assert_eq!(receive(&mut rx, 1), Ok(0));
+    }
+
+    #[test]
+    fn sequence_accepts_any_frame_after_skew_max() {
+        let skew_max = Some(Duration::from_millis(10));
+        let mut rx = Sequence::default();
+        assert_eq!(rx.receive(5, &Duration::ZERO, skew_max), Ok(0));
+        assert_eq!(
+            rx.receive(5, &Duration::from_millis(10), skew_max),
+            Err(RouteError::Duplicate)
+        );
+        assert_eq!(rx.receive(5, &Duration::from_millis(11), skew_max), Ok(0));
+        assert_eq!(rx.receive(3, &Duration::from_millis(30), skew_max), Ok(0));
+    }
+}
@@ -0,0 +1,289 @@
+//! Statistics
+
+use crate::{
+    config::{InterfaceName, PortName, RouterConfigError, VirtualLinksConfig},
+    error::Error,
+    ports::PortError,
+    router::{ForwardReport, RouteError},
+    types::VirtualLinkId,
+};
+
+use core::mem;
+use heapless::LinearMap;
+
+/// Counters of a destination of a virtual link.
+///
+/// All counters wrap around on overflow.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DestinationStats {
+    /// Messages that were sent to the destination.
+    pub sent: usize,
+    /// Messages that could not be sent to the destination.
+    pub failed: usize,
+}
+
+/// Counters of a virtual link.
+///
+/// All counters wrap around on overflow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualLinkStats<const O: usize> {
+    /// Source of the virtual link.
+    pub source: PortName,
+    /// Interface of the second network of a redundant virtual link, if it is
+    /// a source of the virtual link.
+    pub redundant_source: Option<PortName>,
+    /// Times the virtual link was scheduled.
+    pub scheduled: usize,
+    /// Messages that were received from `source`, including messages that
+    /// were not forwarded.
+    pub received: usize,
+    /// Messages that were received from `redundant_source`, including copies
+    /// that were not forwarded.
+    pub redundant_received: usize,
+    /// Messages that were forwarded.
+    pub messages: usize,
+    /// Bytes that were forwarded.
+    pub bytes: usize,
+    /// Receives from the source that returned no message.
+    pub would_block: usize,
+    /// Receives from the source that failed.
+    pub receive_errors: usize,
+    /// Messages that were received but not forwarded, because they were
+    /// invalid or belonged to an unknown virtual link.
+    pub dropped: usize,
+    /// Times the virtual link was not forwarded, because its destinations were
+    /// not ready.
+    pub deferred: usize,
+    /// Messages from the network that were lost according to their sequence
+    /// numbers.
+    pub lost: usize,
+    /// Messages from the network that were dropped, because they repeated the
+    /// sequence number of the last message.
+    pub duplicates: usize,
+    /// Messages from the network that were dropped, because their sequence
+    /// number was older than the one of the last message.
+    pub out_of_order: usize,
+    /// Copies of messages of a redundant virtual link that were dropped,
+    /// because the copy from the other network was forwarded first.
+    pub redundant: usize,
+    /// Reports of forwarded messages that were dropped, because too many
+    /// reports of other virtual links were held back.
+    pub unreported: usize,
+    /// Counters of each destination.
+    pub destinations: LinearMap<PortName, DestinationStats, O>,
+}
+
+impl<const O: usize> VirtualLinkStats<O> {
+    fn reset(&mut self) {
+        let mut destinations = mem::take(&mut self.destinations);
+        destinations
+            .values_mut()
+            .for_each(|d| *d = Default::default());
+        *self = Self {
+            source: mem::take(&mut self.source),
+            redundant_source: self.redundant_source.take(),
+            scheduled: 0,
+            received: 0,
+            redundant_received: 0,
+            messages: 0,
+            bytes: 0,
+            would_block: 0,
+            receive_errors: 0,
+            dropped: 0,
+            deferred: 0,
+            lost: 0,
+            duplicates: 0,
+            out_of_order: 0,
+            redundant: 0,
+            unreported: 0,
+            destinations,
+        };
+    }
+}
+
+/// Counters of a network interface aggregated over all virtual links.
+///
+/// All counters wrap around on overflow.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InterfaceStats {
+    /// Messages that were received from the interface.
+    pub received: usize,
+    /// Receives from the interface that failed.
+    pub receive_errors: usize,
+    /// Messages that were sent to the interface.
+    pub sent: usize,
+    /// Messages that could not be sent to the interface.
+    pub failed: usize,
+}
+
+/// Counters of the router.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouterStats<const I: usize, const O: usize> {
+    vls: LinearMap<VirtualLinkId, VirtualLinkStats<O>, I>,
+}
+
+impl<const I: usize, const O: usize> RouterStats<I, O> {
+    pub(crate) fn new(cfg: &VirtualLinksConfig<I, O>) -> Result<Self, RouterConfigError> {
+        let mut vls = LinearMap::default();
+        for (id, vl) in cfg.iter() {
+            let mut destinations = LinearMap::default();
+            let redundant = vl
+                .redundancy
+                .iter()
+                .filter(|r| vl.dsts.contains(&r.a))
+                .map(|r| &r.b);
+            for d in vl.dsts.iter().chain(redundant) {
+                _ = destinations
+                    .insert(d.clone(), DestinationStats::default())
+                    .or(Err(RouterConfigError::Storage))?;
+            }
+            let redundant_source = vl
+                .redundancy
+                .as_ref()
+                .filter(|r| r.a == vl.src)
+                .map(|r| r.b.clone());
+            let stats = VirtualLinkStats {
+                source: vl.src.clone(),
+                redundant_source,
+                scheduled: 0,
+                received: 0,
+                redundant_received: 0,
+                messages: 0,
+                bytes: 0,
+                would_block: 0,
+                receive_errors: 0,
+                dropped: 0,
+                deferred: 0,
+                lost: 0,
+                duplicates: 0,
+                out_of_order: 0,
+                redundant: 0,
+                unreported: 0,
+                destinations,
+            };
+            _ = vls.insert(*id, stats).or(Err(RouterConfigError::Storage))?;
+        }
+        Ok(Self { vls })
+    }
+
+    /// Counters of a virtual link.
+    pub fn virtual_link(&self, vl: &VirtualLinkId) -> Option<&VirtualLinkStats<O>> {
+        self.vls.get(vl)
+    }
+
+    /// Counters of all virtual links.
+    pub fn virtual_links(&self) -> impl Iterator<Item = (&VirtualLinkId, &VirtualLinkStats<O>)> {
+        self.vls.iter()
+    }
+
+    /// Counters of a network interface.
+    ///
+    /// Shaping of the transmitted frames is counted by the interface itself.
+    pub fn interface(&self, name: &InterfaceName) -> InterfaceStats {
+        self.vls
+            .values()
+            .fold(InterfaceStats::default(), |mut stats, vl| {
+                if vl.source == *name {
+                    stats.received = stats.received.wrapping_add(vl.received);
+                    stats.receive_errors = stats.receive_errors.wrapping_add(vl.receive_errors);
+                }
+                if vl.redundant_source.as_ref() == Some(name) {
+                    stats.received = stats.received.wrapping_add(vl.redundant_received);
+                }
+                if let Some(d) = vl.destinations.get(name) {
+                    stats.sent = stats.sent.wrapping_add(d.sent);
+                    stats.failed = stats.failed.wrapping_add(d.failed);
+                }
+                stats
+            })
+    }
+
+    /// Resets all counters to zero.
+    pub fn reset(&mut self) {
+        self.vls.values_mut().for_each(VirtualLinkStats::reset);
+    }
+
+    pub(crate) fn scheduled(&mut self, vl: &VirtualLinkId) {
+        if let Some(s) = self.vls.get_mut(vl) {
+            increment(&mut s.scheduled);
+        }
+    }
+
+    /// Counts a message that was received on `vl` from its source or, if
+    /// `redundant` is set, from its redundant source.
+    pub(crate) fn received(&mut self, vl: &VirtualLinkId, redundant: bool) {
+        if let Some(s) = self.vls.get_mut(vl) {
+            if redundant {
+                increment(&mut s.redundant_received);
+            } else {
+                increment(&mut s.received);
+            }
+        }
+    }
+
+    pub(crate) fn deferred(&mut self, vl: &VirtualLinkId) {
+        if let Some(s) = self.vls.get_mut(vl) {
+            increment(&mut s.deferred);
+        }
+    }
+
+    pub(crate) fn lost(&mut self, vl: &VirtualLinkId, lost: usize) {
+        if let Some(s) = self.vls.get_mut(vl) {
+            s.lost = s.lost.wrapping_add(lost);
+        }
+    }
+
+    pub(crate) fn out_of_sequence(&mut self, vl: &VirtualLinkId, e: &RouteError, redundant: bool) {
+        let Some(s) = self.vls.get_mut(vl) else {
+            return;
+        };
+        match e {
+            RouteError::Duplicate if redundant => increment(&mut s.redundant),
+            RouteError::Duplicate => increment(&mut s.duplicates),
+            RouteError::OutOfOrder => increment(&mut s.out_of_order),
+            _ => {}
+        }
+    }
+
+    pub(crate) fn unreported(&mut self, vl: &VirtualLinkId) {
+        if let Some(s) = self.vls.get_mut(vl) {
+            increment(&mut s.unreported);
+        }
+    }
+
+    /// Counts a message that was received on `vl` but could not be forwarded.
+    pub(crate) fn failed(&mut self, vl: &VirtualLinkId, e: &Error) {
+        let Some(s) = self.vls.get_mut(vl) else {
+            return;
+        };
+        match e {
+            Error::Port(PortError::WouldBlock) => increment(&mut s.would_block),
+            Error::Port(_) | Error::Interface(_) => increment(&mut s.receive_errors),
+            // Counted by `out_of_sequence`
+            Error::Route(RouteError::Duplicate | RouteError::OutOfOrder) => {}
+            _ => increment(&mut s.dropped),
+        }
+    }
+
+    pub(crate) fn forwarded(&mut self, report: &ForwardReport<O>) {
+        let Some(s) = self.vls.get_mut(&report.vl) else {
+            return;
+        };
+        s.messages = s.messages.wrapping_add(report.messages);
+        s.bytes = s.bytes.wrapping_add(report.bytes);
+        for (name, res) in report.destinations.iter() {
+            if let Some(d) = s.destinations.get_mut(name) {
+                match res {
+                    Ok(()) => increment(&mut d.sent),
+                    Err(_) => increment(&mut d.failed),
+                }
+            }
+        }
+    }
+}
+
+/// Counters wrap around instead of overflowing, so a router that runs long
+/// enough does not panic.
+fn increment(counter: &mut usize) {
+    *counter = counter.wrapping_add(1);
+}
@@ -33,7 +33,7 @@ impl DataRate {
 /// layer should therefore cast this value to the desired size that is
 /// required by the underlying network protocol.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
 pub struct VirtualLinkId(pub u32);
 
 impl Display for VirtualLinkId {
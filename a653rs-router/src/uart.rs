@@ -0,0 +1,127 @@
+//! Framing for serial lines
+
+use crate::network::{Frame, InterfaceError};
+use cobs::{decode_in_place, CobsEncoder};
+use core::mem::size_of;
+
+/// Framing of [`Frame`]s with a payload of up to `MTU` bytes on serial lines.
+///
+/// Each frame is encoded as `COBS(frame + CRC16)` followed by a zero byte, that
+/// marks the end of the frame. The CRC16 uses the USB polynomial and covers the
+/// encoded [`Frame`].
+#[derive(Debug)]
+pub struct UartFrame<const MTU: usize>;
+
+impl<const MTU: usize> UartFrame<MTU> {
+    /// Length of the CRC.
+    pub const CRC_LEN: usize = size_of::<u16>();
+
+    /// Maximum length of a decoded frame including its CRC.
+    pub const MAX_DECODED_LEN: usize = Frame::MAX_HEADER_LEN + MTU + Self::CRC_LEN;
+
+    /// Maximum length of an encoded frame including its terminating zero byte.
+    pub const MAX_ENCODED_LEN: usize = max_encoded_len(Self::MAX_DECODED_LEN) + 1;
+
+    /// The byte marking the end of a frame.
+    pub const TERMINATOR: u8 = 0x0;
+
+    /// Encodes the frame into `encoded` and returns the encoded portion of
+    /// `encoded` including the terminating zero byte.
+    ///
+    /// # Errors
+    /// Returns an error if the payload is larger than `MTU` or `encoded` is too
+    /// small.
+    pub fn encode<'a>(
+        frame: &Frame<'_>,
+        encoded: &'a mut [u8],
+    ) -> Result<&'a [u8], InterfaceError> {
+        if frame.payload.len() > MTU {
+            return Err(InterfaceError::InsufficientBuffer);
+        }
+        let mut header = [0u8; Frame::MAX_HEADER_LEN];
+        let header = Frame {
+            payload: &[],
+            ..*frame
+        }
+        .encode(&mut header)?;
+        let mut crc = crc16::State::<crc16::USB>::new();
+        crc.update(header);
+        crc.update(frame.payload);
+        let crc = crc.get().to_be_bytes();
+
+        let mut encoder = CobsEncoder::new(encoded);
+        encoder
+            .push(header)
+            .and_then(|_| encoder.push(frame.payload))
+            .and_then(|_| encoder.push(&crc))
+            .or(Err(InterfaceError::InsufficientBuffer))?;
+        let len = encoder
+            .finalize()
+            .or(Err(InterfaceError::InsufficientBuffer))?;
+        let terminator = encoded
+            .get_mut(len)
+            .ok_or(InterfaceError::InsufficientBuffer)?;
+        *terminator = Self::TERMINATOR;
+        Ok(&encoded[..=len])
+    }
+
+    /// Decodes a frame from `buf` in place.
+    ///
+    /// Decoding stops at the first zero byte, if any.
+    ///
+    /// # Errors
+    /// Returns an error if `buf` is not a valid COBS encoding or the CRC does not
+    /// match.
+    pub fn decode(buf: &mut [u8]) -> Result<Frame<'_>, InterfaceError> {
+        let len = decode_in_place(buf).or(Err(InterfaceError::InvalidData))?;
+        if len < Self::CRC_LEN {
+            return Err(InterfaceError::InvalidData);
+        }
+        let (msg, crc) = buf[..len].split_at(len - Self::CRC_LEN);
+        let crc = u16::from_be_bytes([crc[0], crc[1]]);
+        if crc != crc16::State::<crc16::USB>::calculate(msg) {
+            return Err(InterfaceError::InvalidData);
+        }
+        Frame::decode(msg)
+    }
+}
+
+/// Maximum length of the COBS encoding of `raw_len` bytes.
+const fn max_encoded_len(raw_len: usize) -> usize {
+    let overhead = if raw_len == 0 {
+        // Just 0xff
+        1
+    } else {
+        raw_len.div_ceil(254)
+    };
+    raw_len + overhead
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::VirtualLinkId;
+
+    #[test]
+    fn uart_frame_roundtrip() {
+        let mut buf = [0u8; UartFrame::<4>::MAX_ENCODED_LEN];
+        let frame = Frame::new(VirtualLinkId::from(0x100u16), &[0, 1, 0, 2]);
+        let encoded = UartFrame::<4>::encode(&frame, &mut buf).unwrap();
+        assert_eq!(encoded.last(), Some(&UartFrame::<4>::TERMINATOR));
+        assert!(!encoded[..encoded.len() - 1].contains(&UartFrame::<4>::TERMINATOR));
+        assert_eq!(UartFrame::<4>::decode(&mut buf).unwrap(), frame);
+
+        let encoded = UartFrame::<4>::encode(&frame, &mut buf).unwrap().len();
+        buf[encoded - 2] ^= 0x01;
+        assert_eq!(
+            UartFrame::<4>::decode(&mut buf),
+            Err(InterfaceError::InvalidData)
+        );
+
+        let frame = Frame::new(VirtualLinkId::from(1u16), &[0; 5]);
+        assert_eq!(
+            UartFrame::<4>::encode(&frame, &mut buf),
+            Err(InterfaceError::InsufficientBuffer)
+        );
+    }
+}